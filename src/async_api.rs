@@ -0,0 +1,671 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Async variant of `Api`, enabled with the `async` feature. Queries return futures and
+//! subscriptions return streams, so the api can be used from async runtimes such as tokio without
+//! blocking their worker threads.
+//!
+//! The futures do not depend on a specific executor. They are woken up by the transport as soon
+//! as the response has been received, or by a timer thread of the api once the request timeout has
+//! passed. Dropping a future or stream cancels its request or subscription in the transport.
+
+use std::convert::TryFrom;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use codec::{Decode, Encode};
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
+use futures::{Stream, StreamExt};
+use log::{debug, info};
+use metadata::RuntimeMetadataPrefixed;
+use serde_json::Value;
+use sp_core::storage::StorageKey;
+use sp_version::RuntimeVersion;
+
+use crate::node_metadata::{Metadata, MetadataError};
+use crate::rpc::{
+    self, json_req, timeout_response, OnMessageFn, RpcError, RpcTransport, WsRpcClient,
+    DEFAULT_REQUEST_TIMEOUT,
+};
+use crate::runtime::{DefaultRuntime, Runtime};
+use crate::signer::Signer;
+use crate::utils::*;
+use crate::{
+    decode_hash, decode_storage, extrinsic_result, AccountData, AccountInfo, ApiError,
+    ApiResult, Hash, TransactionStatus, XtStatus, DEFAULT_FINALIZATION_TIMEOUT,
+};
+
+/// Async counterpart of `Api`. See the module documentation.
+#[derive(Clone)]
+pub struct AsyncApi<P, Client = WsRpcClient, R = DefaultRuntime>
+where
+    P: Signer<R::AccountId>,
+    Client: RpcTransport,
+    R: Runtime,
+{
+    client: Transport<Client>,
+    pub signer: Option<P>,
+    pub genesis_hash: R::Hash,
+    pub metadata: Metadata,
+    pub runtime_version: RuntimeVersion,
+    /// Time to wait for the response to a request.
+    pub request_timeout: Duration,
+    /// Time to wait for an extrinsic sent with `XtStatus::InBlock` or `XtStatus::Finalized` to be
+    /// included in a block or finalized.
+    pub finalization_timeout: Duration,
+    runtime: PhantomData<R>,
+}
+
+impl<P> AsyncApi<P, WsRpcClient>
+where
+    P: Signer,
+{
+    /// Connects to the node at `url`. The connection is established on a separate thread, as
+    /// `WsRpcClient::new` blocks until it is.
+    pub async fn new(url: String) -> ApiResult<Self> {
+        let (client_in, client_out) = oneshot::channel();
+        thread::Builder::new()
+            .name("connect".to_owned())
+            .spawn(move || {
+                let _ = client_in.send(WsRpcClient::new(&url));
+            })
+            .map_err(RpcError::from)?;
+        let client = client_out
+            .await
+            .map_err(|_| RpcError::ConnectionLost)?
+            .map_err(RpcError::from)?;
+        Self::new_with_transport(client).await
+    }
+}
+
+impl<P, Client, R> AsyncApi<P, Client, R>
+where
+    P: Signer<R::AccountId>,
+    Client: RpcTransport,
+    R: Runtime,
+{
+    pub async fn new_with_transport(client: Client) -> ApiResult<Self> {
+        let client = Transport::new(client).map_err(RpcError::from)?;
+        let genesis_hash = Self::_get_genesis_hash(&client).await?;
+        info!("Got genesis hash: {:?}", genesis_hash);
+
+        let meta = Self::_get_metadata(&client, DEFAULT_REQUEST_TIMEOUT).await?;
+        let metadata = Metadata::try_from(meta)?;
+        debug!("Metadata: {:?}", metadata);
+
+        let runtime_version = Self::_get_runtime_version(&client, DEFAULT_REQUEST_TIMEOUT).await?;
+        info!("Runtime Version: {:?}", runtime_version);
+
+        Ok(Self {
+            client,
+            signer: None,
+            genesis_hash,
+            metadata,
+            runtime_version,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            finalization_timeout: DEFAULT_FINALIZATION_TIMEOUT,
+            runtime: PhantomData,
+        })
+    }
+
+    pub fn set_signer(mut self, signer: P) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    pub fn set_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn set_finalization_timeout(mut self, timeout: Duration) -> Self {
+        self.finalization_timeout = timeout;
+        self
+    }
+
+    /// Sends `jsonreq` with the request timeout of the api.
+    fn request(&self, jsonreq: Value) -> impl Future<Output = ApiResult<String>> {
+        self.client.request(jsonreq, self.request_timeout)
+    }
+
+    fn _get_genesis_hash(client: &Transport<Client>) -> impl Future<Output = ApiResult<R::Hash>> {
+        let res = client.request(json_req::chain_get_genesis_hash(), DEFAULT_REQUEST_TIMEOUT);
+        async move { decode_hash(res.await?) }
+    }
+
+    fn _get_runtime_version(
+        client: &Transport<Client>,
+        timeout: Duration,
+    ) -> impl Future<Output = ApiResult<RuntimeVersion>> {
+        let res = client.request(json_req::state_get_runtime_version(), timeout);
+        async move {
+            let version_str = res.await?;
+            debug!("got the following runtime version (raw): {}", version_str);
+            Ok(serde_json::from_str(&version_str)?)
+        }
+    }
+
+    fn _get_metadata(
+        client: &Transport<Client>,
+        timeout: Duration,
+    ) -> impl Future<Output = ApiResult<RuntimeMetadataPrefixed>> {
+        let res = client.request(json_req::state_get_metadata(), timeout);
+        async move {
+            let _unhex = hexstr_to_vec(res.await?)?;
+            let mut _om = _unhex.as_slice();
+            Ok(RuntimeMetadataPrefixed::decode(&mut _om)?)
+        }
+    }
+
+    pub fn get_metadata(&self) -> impl Future<Output = ApiResult<RuntimeMetadataPrefixed>> {
+        Self::_get_metadata(&self.client, self.request_timeout)
+    }
+
+    pub fn get_spec_version(&self) -> impl Future<Output = ApiResult<u32>> {
+        let version = Self::_get_runtime_version(&self.client, self.request_timeout);
+        async move { Ok(version.await?.spec_version) }
+    }
+
+    pub fn get_genesis_hash(&self) -> impl Future<Output = ApiResult<R::Hash>> {
+        let res = self.request(json_req::chain_get_genesis_hash());
+        async move { decode_hash(res.await?) }
+    }
+
+    pub fn get_nonce(&self) -> impl Future<Output = ApiResult<R::Index>> {
+        let info = self
+            .signer
+            .as_ref()
+            .map(|signer| self.get_account_info(&signer.account_id()));
+        async move {
+            match info {
+                Some(info) => Ok(info.await?.map_or(R::Index::default(), |info| info.nonce)),
+                None => Err(ApiError::NoSigner),
+            }
+        }
+    }
+
+    pub fn get_account_info(
+        &self,
+        address: &R::AccountId,
+    ) -> impl Future<Output = ApiResult<Option<AccountInfo<R>>>> {
+        let storagekey = self
+            .metadata
+            .module("System")
+            .and_then(|module| module.storage("Account"))
+            .and_then(|storage| storage.get_map::<R::AccountId, AccountInfo<R>>())
+            .map(|map| map.key(address.clone()));
+        self.get_storage(storagekey)
+    }
+
+    pub fn get_account_data(
+        &self,
+        address: &R::AccountId,
+    ) -> impl Future<Output = ApiResult<Option<AccountData<R>>>> {
+        let info = self.get_account_info(address);
+        async move { Ok(info.await?.map(|info| info.data)) }
+    }
+
+    pub fn get_finalized_head(&self) -> impl Future<Output = ApiResult<String>> {
+        self.request(json_req::chain_get_finalized_head())
+    }
+
+    pub fn get_header(&self, hash: Option<R::Hash>) -> impl Future<Output = ApiResult<String>> {
+        self.request(json_req::chain_get_header(hash))
+    }
+
+    pub fn get_block(&self, hash: Option<R::Hash>) -> impl Future<Output = ApiResult<String>> {
+        self.request(json_req::chain_get_block(hash))
+    }
+
+    /// Sends a raw json request. Its `id` is replaced by the transport.
+    pub fn get_request(&self, jsonreq: Value) -> impl Future<Output = ApiResult<String>> {
+        self.request(jsonreq)
+    }
+
+    pub fn get_storage_value<V: Decode + Clone>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+    ) -> impl Future<Output = ApiResult<Option<V>>> {
+        let storagekey = self
+            .metadata
+            .module(storage_prefix)
+            .and_then(|module| module.storage(storage_key_name))
+            .and_then(|storage| storage.get_value())
+            .map(|value| value.key());
+        self.get_storage(storagekey)
+    }
+
+    pub fn get_storage_map<K: Encode, V: Decode + Clone>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        map_key: K,
+    ) -> impl Future<Output = ApiResult<Option<V>>> {
+        let storagekey = self
+            .metadata
+            .module(storage_prefix)
+            .and_then(|module| module.storage(storage_key_name))
+            .and_then(|storage| storage.get_map::<K, V>())
+            .map(|map| map.key(map_key));
+        self.get_storage(storagekey)
+    }
+
+    pub fn get_storage_double_map<K: Encode, Q: Encode, V: Decode + Clone>(
+        &self,
+        storage_prefix: &'static str,
+        storage_key_name: &'static str,
+        first: K,
+        second: Q,
+    ) -> impl Future<Output = ApiResult<Option<V>>> {
+        let storagekey = self
+            .metadata
+            .module(storage_prefix)
+            .and_then(|module| module.storage(storage_key_name))
+            .and_then(|storage| storage.get_double_map::<K, Q, V>())
+            .map(|map| map.key(first, second));
+        self.get_storage(storagekey)
+    }
+
+    pub fn get_storage_by_key_hash<V: Decode>(
+        &self,
+        hash: Vec<u8>,
+    ) -> impl Future<Output = ApiResult<Option<V>>> {
+        let mut keyhash_str = hex::encode(hash);
+        keyhash_str.insert_str(0, "0x");
+        let res = self.request(json_req::state_get_storage(&keyhash_str));
+        async move { decode_storage(res.await?) }
+    }
+
+    /// Fetches the storage at a key looked up from the metadata. Errors of the lookup are
+    /// returned when the future is polled.
+    fn get_storage<V: Decode>(
+        &self,
+        storagekey: Result<StorageKey, MetadataError>,
+    ) -> impl Future<Output = ApiResult<Option<V>>> {
+        let res = storagekey.map(|storagekey| {
+            info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
+            self.get_storage_by_key_hash(storagekey.0)
+        });
+        async move { res?.await }
+    }
+
+    /// Submits an extrinsic without watching its status and returns the extrinsic's hash.
+    pub fn submit_extrinsic(
+        &self,
+        xthex_prefixed: String,
+    ) -> impl Future<Output = ApiResult<Hash>> {
+        debug!("submitting extrinsic: {:?}", xthex_prefixed);
+        let res = self.request(json_req::author_submit_extrinsic(&xthex_prefixed));
+        async move { Ok(hexstr_to_hash(res.await?)?) }
+    }
+
+    pub fn send_extrinsic(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+    ) -> impl Future<Output = ApiResult<Option<Hash>>> {
+        debug!("sending extrinsic: {:?}", xthex_prefixed);
+        let jsonreq = json_req::author_submit_and_watch_extrinsic(&xthex_prefixed);
+        let status = match exit_on {
+            XtStatus::Finalized => Ok(rpc::on_extrinsic_msg_until_finalized as OnMessageFn),
+            XtStatus::InBlock => Ok(rpc::on_extrinsic_msg_until_in_block as OnMessageFn),
+            XtStatus::Ready => Ok(rpc::on_extrinsic_msg_until_ready as OnMessageFn),
+            _ => Err(ApiError::UnsupportedXtStatus(exit_on)),
+        }
+        .and_then(|on_message_fn| {
+            let timeout = match exit_on {
+                XtStatus::Finalized | XtStatus::InBlock => self.finalization_timeout,
+                _ => self.request_timeout,
+            };
+            self.client.subscribe(jsonreq, on_message_fn, Some(timeout))
+        });
+        async move {
+            let msg = status?
+                .next()
+                .await
+                .ok_or(RpcError::ConnectionLost)?;
+            extrinsic_result(&msg)
+        }
+    }
+
+    /// Submits an extrinsic and returns the stream of all of its status updates. See
+    /// `Api::watch_extrinsic`.
+    pub fn watch_extrinsic(
+        &self,
+        xthex_prefixed: String,
+    ) -> ApiResult<impl Stream<Item = ApiResult<TransactionStatus>>> {
+        debug!("watching extrinsic: {:?}", xthex_prefixed);
+        let jsonreq = json_req::author_submit_and_watch_extrinsic(&xthex_prefixed);
+        let status = self
+            .client
+            .subscribe(jsonreq, rpc::on_transaction_status_msg, None)?;
+        Ok(status.map(|msg| Ok(rpc::parse_transaction_status(&msg)?)))
+    }
+
+    /// Stream of the encoded event records of every block.
+    pub fn subscribe_events(&self) -> ApiResult<Subscription<Client>> {
+        debug!("subscribing to events");
+        let key = storage_key_hash("System", "Events", None);
+        let jsonreq = json_req::state_subscribe_storage(&key);
+        self.client.subscribe(jsonreq, rpc::on_subscription_msg, None)
+    }
+
+    /// Stream of the headers of new blocks, as json strings.
+    pub fn subscribe_new_heads(&self) -> ApiResult<Subscription<Client>> {
+        debug!("subscribing to new heads");
+        let jsonreq = json_req::chain_subscribe_new_heads();
+        self.client.subscribe(jsonreq, rpc::on_notification_msg, None)
+    }
+
+    /// Stream of the headers of newly finalized blocks, as json strings.
+    pub fn subscribe_finalized_heads(&self) -> ApiResult<Subscription<Client>> {
+        debug!("subscribing to finalized heads");
+        let jsonreq = json_req::chain_subscribe_finalized_heads();
+        self.client.subscribe(jsonreq, rpc::on_notification_msg, None)
+    }
+
+    /// Stream of the url of the node, every time the connection has been re-established after it
+    /// was lost. See `Api::subscribe_reconnects`.
+    pub fn subscribe_reconnects(&self) -> UnboundedReceiver<String> {
+        let (result_in, result_out) = unbounded();
+        self.client.client.subscribe_reconnects(result_in.into());
+        result_out
+    }
+}
+
+/// The transport of an `AsyncApi`, shared by the futures and streams it returns, and the timer
+/// of their requests.
+struct Transport<Client> {
+    client: Arc<Client>,
+    timer: Timer,
+}
+
+impl<Client> Clone for Transport<Client> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            timer: self.timer.clone(),
+        }
+    }
+}
+
+impl<Client: RpcTransport> Transport<Client> {
+    fn new(client: Client) -> std::io::Result<Self> {
+        Ok(Self {
+            client: Arc::new(client),
+            timer: Timer::start()?,
+        })
+    }
+
+    /// Sends the request right away and returns a future resolving to its `result`, or to
+    /// `ApiError::Timeout` if there is none within `timeout`. The future does not borrow the
+    /// client, so it can be moved to another task.
+    fn request(&self, jsonreq: Value, timeout: Duration) -> impl Future<Output = ApiResult<String>> {
+        let result_out = self.subscribe(jsonreq, rpc::on_get_request_msg, Some(timeout));
+        async move {
+            let msg = result_out?
+                .next()
+                .await
+                .ok_or(RpcError::ConnectionLost)?;
+            Ok(rpc::parse_response(&msg)?)
+        }
+    }
+
+    /// Starts the subscription. If `timeout` is set, a `timeout_response` is delivered once it
+    /// has passed.
+    fn subscribe(
+        &self,
+        jsonreq: Value,
+        on_message_fn: OnMessageFn,
+        timeout: Option<Duration>,
+    ) -> ApiResult<Subscription<Client>> {
+        let (result_in, result_out) = unbounded();
+        if let Some(timeout) = timeout {
+            self.timer.notify(result_in.clone(), timeout);
+        }
+        let id = self
+            .client
+            .subscribe(jsonreq, result_in.into(), on_message_fn)?;
+        Ok(Subscription {
+            messages: result_out,
+            client: self.client.clone(),
+            id,
+        })
+    }
+}
+
+/// Stream of the messages of a request or subscription. Cancels it in the transport when
+/// dropped, so requests and subscriptions nobody waits for are not kept.
+pub struct Subscription<Client: RpcTransport> {
+    messages: UnboundedReceiver<String>,
+    client: Arc<Client>,
+    id: String,
+}
+
+impl<Client: RpcTransport> Stream for Subscription<Client> {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<String>> {
+        self.messages.poll_next_unpin(cx)
+    }
+}
+
+impl<Client: RpcTransport> Drop for Subscription<Client> {
+    fn drop(&mut self) {
+        self.client.cancel(&self.id);
+    }
+}
+
+type Deadline = (Instant, Duration, UnboundedSender<String>);
+
+/// Delivers a `timeout_response` to requests that are not answered in time. One thread per api,
+/// it ends once the api and all requests with a pending deadline are dropped.
+#[derive(Clone)]
+struct Timer(Sender<Deadline>);
+
+impl Timer {
+    fn start() -> std::io::Result<Self> {
+        let (deadlines_in, deadlines_out) = channel::<Deadline>();
+        thread::Builder::new()
+            .name("timer".to_owned())
+            .spawn(move || {
+                let mut deadlines: Vec<Deadline> = Vec::new();
+                let mut open = true;
+                loop {
+                    let now = Instant::now();
+                    deadlines.retain(|(deadline, timeout, result_in)| {
+                        if *deadline <= now {
+                            let _ = result_in.unbounded_send(timeout_response(*timeout));
+                            return false;
+                        }
+                        !result_in.is_closed()
+                    });
+                    let next = deadlines.iter().map(|(deadline, _, _)| *deadline).min();
+                    let received = match (next, open) {
+                        (None, false) => break,
+                        (Some(next), false) => {
+                            thread::sleep(next.saturating_duration_since(now));
+                            continue;
+                        }
+                        (Some(next), true) => {
+                            deadlines_out.recv_timeout(next.saturating_duration_since(now))
+                        }
+                        (None, true) => deadlines_out
+                            .recv()
+                            .map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+                    match received {
+                        Ok(deadline) => deadlines.push(deadline),
+                        Err(RecvTimeoutError::Timeout) => {}
+                        Err(RecvTimeoutError::Disconnected) => open = false,
+                    }
+                }
+            })?;
+        Ok(Timer(deadlines_in))
+    }
+
+    /// Delivers a `timeout_response` to `result_in` once `timeout` has passed.
+    fn notify(&self, result_in: UnboundedSender<String>, timeout: Duration) {
+        let _ = self.0.send((Instant::now() + timeout, timeout, result_in));
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use crate::rpc::HttpRpcClient;
+    use crate::test_support::fixtures::{node, wait_for_request, TIMEOUT};
+    use crate::test_support::ScriptedStatus;
+    use futures::executor::block_on;
+    use keyring::AccountKeyring;
+    use sp_core::sr25519;
+    use std::net::TcpListener;
+
+    #[test]
+    fn async_api_reads_storage_and_nonce() {
+        let node = node().spawn().unwrap();
+        let api = block_on(AsyncApi::<sr25519::Pair>::new(node.url()))
+            .unwrap()
+            .set_signer(AccountKeyring::Alice.pair());
+        let key = api
+            .metadata
+            .module("Balances")
+            .unwrap()
+            .storage("TotalIssuance")
+            .unwrap()
+            .get_value()
+            .unwrap()
+            .key();
+        node.set_storage(key.0, 1_000u128.encode());
+
+        let total: Option<u128> =
+            block_on(api.get_storage_value("Balances", "TotalIssuance")).unwrap();
+
+        assert_eq!(api.genesis_hash, Hash::repeat_byte(0x01));
+        assert_eq!(total, Some(1_000));
+        assert_eq!(block_on(api.get_nonce()).unwrap(), 0);
+    }
+
+    #[test]
+    fn async_api_sends_and_watches_extrinsics() {
+        let block = Hash::repeat_byte(0x03);
+        let node = node()
+            .with_extrinsic_statuses(vec![
+                ScriptedStatus::Ready,
+                ScriptedStatus::InBlock(block),
+                ScriptedStatus::Finalized(block),
+            ])
+            .spawn()
+            .unwrap();
+        let api = block_on(AsyncApi::<sr25519::Pair>::new(node.url())).unwrap();
+
+        let hash = block_on(api.send_extrinsic("0x00".to_string(), XtStatus::InBlock)).unwrap();
+        let statuses: Vec<TransactionStatus> = block_on(
+            api.watch_extrinsic("0x01".to_string())
+                .unwrap()
+                .map(|status| status.unwrap())
+                .collect(),
+        );
+
+        assert_eq!(hash, Some(block));
+        assert_eq!(
+            statuses,
+            vec![
+                TransactionStatus::Ready,
+                TransactionStatus::InBlock(block),
+                TransactionStatus::Finalized(block),
+            ]
+        );
+        assert_eq!(node.submitted_extrinsics(), vec!["0x00", "0x01"]);
+    }
+
+    #[test]
+    fn connecting_to_a_missing_node_fails() {
+        let url = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("ws://{}", listener.local_addr().unwrap())
+        };
+        assert!(block_on(AsyncApi::<sr25519::Pair>::new(url)).is_err());
+    }
+
+    #[test]
+    fn http_requests_do_not_block_until_polled() {
+        // accepts connections, but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = HttpRpcClient::new(&format!("http://{}", listener.local_addr().unwrap()))
+            .set_timeout(Duration::from_millis(200));
+
+        let transport = Transport::new(client).unwrap();
+
+        let start = Instant::now();
+        let jsonreq = json_req::chain_get_genesis_hash();
+        let response = transport.request(jsonreq, DEFAULT_REQUEST_TIMEOUT);
+        assert!(start.elapsed() < Duration::from_millis(200));
+
+        match block_on(response) {
+            Err(ApiError::Timeout(timeout)) => assert_eq!(timeout, Duration::from_millis(200)),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn unanswered_request_times_out_and_is_forgotten() {
+        let node = node().with_unanswered("chain_getHeader").spawn().unwrap();
+        let client = WsRpcClient::new(&node.url()).unwrap();
+        let api = block_on(AsyncApi::<sr25519::Pair>::new_with_transport(client.clone()))
+            .unwrap()
+            .set_request_timeout(TIMEOUT);
+
+        match block_on(api.get_header(None)) {
+            Err(ApiError::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(client.pending_requests(), 0);
+    }
+
+    #[test]
+    fn dropped_futures_and_streams_are_cancelled() {
+        let events = hexstr_to_vec(storage_key_hash("System", "Events", None)).unwrap();
+        let node = node()
+            .with_storage(events, vec![0])
+            .with_unanswered("chain_getHeader")
+            .spawn()
+            .unwrap();
+        let client = WsRpcClient::new(&node.url()).unwrap();
+        let api = block_on(AsyncApi::<sr25519::Pair>::new_with_transport(client.clone())).unwrap();
+
+        let header = api.get_header(None);
+        wait_for_request(&node, "chain_getHeader");
+        assert_eq!(client.pending_requests(), 1);
+        drop(header);
+        assert_eq!(client.pending_requests(), 0);
+
+        let mut events = api.subscribe_events().unwrap();
+        assert_eq!(block_on(events.next()), Some("0x00".to_string()));
+        drop(events);
+        let unsubscribe = wait_for_request(&node, "state_unsubscribeStorage");
+        assert_eq!(unsubscribe["params"].as_array().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,113 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Outcome of applying an extrinsic to the state of a block without including it, see
+//! `Api::dry_run`.
+
+use sp_runtime::ApplyExtrinsicResult;
+
+use crate::node_metadata::{dispatch_error_description, Metadata, ModuleErrorMetadata};
+
+#[derive(Debug)]
+pub struct DryRunResult {
+    /// `Err` if the extrinsic is invalid, e.g. for its nonce or because the fees cannot be paid.
+    /// `Ok(Err(_))` if it is valid, but its dispatch fails, e.g. with `DispatchError::Module`.
+    pub result: ApplyExtrinsicResult,
+    /// Name and documentation of the error if the dispatch failed with `DispatchError::Module`
+    pub module_error: Option<ModuleErrorMetadata>,
+}
+
+impl DryRunResult {
+    pub fn new(result: ApplyExtrinsicResult, metadata: &Metadata) -> Self {
+        let module_error = match &result {
+            Ok(Err(error)) => metadata.dispatch_error(error).cloned(),
+            _ => None,
+        };
+        Self {
+            result,
+            module_error,
+        }
+    }
+
+    /// Whether the extrinsic is valid and its dispatch succeeds.
+    pub fn is_success(&self) -> bool {
+        matches!(self.result, Ok(Ok(())))
+    }
+
+    /// Describes why the extrinsic is invalid or its dispatch fails, e.g.
+    /// `Balances::InsufficientBalance: Balance too low to send value`, or `None` on success.
+    pub fn error_description(&self) -> Option<String> {
+        match &self.result {
+            Ok(Ok(())) => None,
+            Ok(Err(error)) => Some(dispatch_error_description(error, self.module_error.as_ref())),
+            Err(error) => Some(format!("Invalid extrinsic: {:?}", error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_runtime::transaction_validity::InvalidTransaction;
+    use sp_runtime::DispatchError;
+    use std::convert::TryFrom;
+
+    fn metadata() -> Metadata {
+        Metadata::try_from(node_runtime::Runtime::metadata()).unwrap()
+    }
+
+    #[test]
+    fn module_errors_are_resolved() {
+        // Balances::InsufficientBalance in node_runtime
+        let result = Ok(Err(DispatchError::Module {
+            index: 6,
+            error: 3,
+            message: None,
+        }));
+
+        let dry_run = DryRunResult::new(result, &metadata());
+
+        assert!(!dry_run.is_success());
+        assert_eq!(dry_run.module_error.as_ref().unwrap().name, "InsufficientBalance");
+        assert!(dry_run
+            .error_description()
+            .unwrap()
+            .starts_with("Balances::InsufficientBalance"));
+    }
+
+    #[test]
+    fn invalid_extrinsics_are_reported() {
+        let result: ApplyExtrinsicResult = Err(InvalidTransaction::Stale.into());
+
+        let dry_run = DryRunResult::new(result.clone(), &metadata());
+
+        assert!(!dry_run.is_success());
+        assert_eq!(dry_run.module_error, None);
+        assert_eq!(
+            dry_run.error_description().unwrap(),
+            format!("Invalid extrinsic: {:?}", result.unwrap_err())
+        );
+    }
+
+    #[test]
+    fn successful_dispatch_has_no_error() {
+        let dry_run = DryRunResult::new(Ok(Ok(())), &metadata());
+
+        assert!(dry_run.is_success());
+        assert_eq!(dry_run.error_description(), None);
+    }
+}
@@ -25,7 +25,10 @@ use sp_runtime::DispatchError;
 use support::weights::DispatchInfo;
 use system::Phase;
 
-use crate::node_metadata::{EventArg, Metadata, MetadataError};
+use crate::node_metadata::{
+    dispatch_error_description, EventArg, Metadata, MetadataError, ModuleErrorMetadata,
+};
+use crate::runtime::{DefaultRuntime, Runtime};
 
 /// Event for the System module.
 #[derive(Clone, Debug, Decode)]
@@ -62,6 +65,77 @@ pub enum EventsError {
     Metadata(#[from] MetadataError),
     #[error("Type Sizes Unavailable: {0:?}")]
     TypeSizeUnavailable(String),
+    #[error("No ExtrinsicSuccess or ExtrinsicFailed event for extrinsic {0}")]
+    MissingExtrinsicOutcome(u32),
+}
+
+/// Outcome of an extrinsic included in a block, see `Api::send_extrinsic_with_report`.
+#[derive(Debug)]
+pub struct ExtrinsicReport<H = crate::Hash> {
+    /// The block the extrinsic was included in
+    pub block_hash: H,
+    /// The index of the extrinsic in the block body
+    pub extrinsic_index: u32,
+    /// `Err` if the dispatch failed, e.g. with `DispatchError::Module`
+    pub result: Result<(), DispatchError>,
+    /// Name and documentation of the error if the dispatch failed with `DispatchError::Module`
+    pub module_error: Option<ModuleErrorMetadata>,
+    pub dispatch_info: DispatchInfo,
+    /// The events emitted by the extrinsic besides `ExtrinsicSuccess` or `ExtrinsicFailed`
+    pub events: Vec<RawEvent>,
+}
+
+impl<H> ExtrinsicReport<H> {
+    /// Picks the events of the extrinsic at `extrinsic_index` from all events of the block.
+    pub fn new(
+        block_hash: H,
+        extrinsic_index: u32,
+        block_events: Vec<(Phase, RuntimeEvent)>,
+        metadata: &Metadata,
+    ) -> Result<Self, EventsError> {
+        let mut outcome = None;
+        let mut events = Vec::new();
+        for (phase, event) in block_events {
+            match phase {
+                Phase::ApplyExtrinsic(index) if index == extrinsic_index => {}
+                _ => continue,
+            }
+            match event {
+                RuntimeEvent::System(SystemEvent::ExtrinsicSuccess(info)) => {
+                    outcome = Some((Ok(()), info))
+                }
+                RuntimeEvent::System(SystemEvent::ExtrinsicFailed(error, info)) => {
+                    outcome = Some((Err(error), info))
+                }
+                RuntimeEvent::Raw(raw) => events.push(raw),
+            }
+        }
+        let (result, dispatch_info) =
+            outcome.ok_or(EventsError::MissingExtrinsicOutcome(extrinsic_index))?;
+        let module_error = match &result {
+            Err(error) => metadata.dispatch_error(error).cloned(),
+            Ok(()) => None,
+        };
+        Ok(Self {
+            block_hash,
+            extrinsic_index,
+            result,
+            module_error,
+            dispatch_info,
+            events,
+        })
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// Describes the dispatch error, e.g. `Balances::InsufficientBalance: Balance too low to send
+    /// value`, or `None` on success.
+    pub fn error_description(&self) -> Option<String> {
+        let error = self.result.as_ref().err()?;
+        Some(dispatch_error_description(error, self.module_error.as_ref()))
+    }
 }
 
 pub struct EventsDecoder {
@@ -74,6 +148,13 @@ impl TryFrom<Metadata> for EventsDecoder {
     type Error = EventsError;
 
     fn try_from(metadata: Metadata) -> Result<Self, Self::Error> {
+        Self::for_runtime::<DefaultRuntime>(metadata)
+    }
+}
+
+impl EventsDecoder {
+    /// Decoder of the events of a chain with the types of runtime `R`.
+    pub fn for_runtime<R: Runtime>(metadata: Metadata) -> Result<Self, EventsError> {
         let mut decoder = Self {
             metadata,
             type_sizes: HashMap::new(),
@@ -94,18 +175,17 @@ impl TryFrom<Metadata> for EventsDecoder {
         decoder.register_type_size::<u32>("AuthorityIndex")?;
         decoder.register_type_size::<u64>("AuthorityWeight")?;
         decoder.register_type_size::<u32>("MemberCount")?;
-        decoder.register_type_size::<crate::AccountId>("AccountId")?;
-        decoder.register_type_size::<crate::BlockNumber>("BlockNumber")?;
-        decoder.register_type_size::<crate::Hash>("Hash")?;
-        decoder.register_type_size::<crate::Balance>("Balance")?;
+        decoder.register_type_size::<R::AccountId>("AccountId")?;
+        decoder.register_type_size::<R::BlockNumber>("BlockNumber")?;
+        decoder.register_type_size::<R::Hash>("Hash")?;
+        decoder.register_type_size::<R::Balance>("Balance")?;
+        decoder.register_type_size::<R::Index>("Index")?;
         // VoteThreshold enum index
         decoder.register_type_size::<u8>("VoteThreshold")?;
 
         Ok(decoder)
     }
-}
 
-impl EventsDecoder {
     pub fn register_type_size<U>(&mut self, name: &str) -> Result<usize, EventsError>
     where
         U: Default + Codec + Send + 'static,
@@ -203,6 +283,12 @@ impl EventsDecoder {
                 log::debug!("Decoding system event, intput: {:?}", input);
                 let system_event = SystemEvent::decode(input)?;
                 log::debug!("Decoding successful, system_event: {:?}", system_event);
+                if let SystemEvent::ExtrinsicFailed(error, _) = &system_event {
+                    log::debug!(
+                        "extrinsic failed: {}",
+                        self.metadata.describe_dispatch_error(error)
+                    );
+                }
                 RuntimeEvent::System(system_event)
             } else {
                 let event_variant = input.read_byte()?;
@@ -240,3 +326,105 @@ impl EventsDecoder {
         Ok(r)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Metadata {
+        Metadata::try_from(node_runtime::Runtime::metadata()).unwrap()
+    }
+
+    fn raw(variant: &str) -> RuntimeEvent {
+        RuntimeEvent::Raw(RawEvent {
+            module: "Balances".to_string(),
+            variant: variant.to_string(),
+            data: vec![],
+        })
+    }
+
+    #[test]
+    fn report_contains_only_events_of_the_extrinsic() {
+        let block_events = vec![
+            (
+                Phase::ApplyExtrinsic(0),
+                RuntimeEvent::System(SystemEvent::ExtrinsicSuccess(DispatchInfo::default())),
+            ),
+            (Phase::ApplyExtrinsic(1), raw("Transfer")),
+            (
+                Phase::ApplyExtrinsic(1),
+                RuntimeEvent::System(SystemEvent::ExtrinsicFailed(
+                    DispatchError::BadOrigin,
+                    DispatchInfo::default(),
+                )),
+            ),
+            (Phase::Finalization, raw("Deposit")),
+        ];
+
+        let report =
+            ExtrinsicReport::new(crate::Hash::zero(), 1, block_events, &metadata()).unwrap();
+
+        assert_eq!(report.result, Err(DispatchError::BadOrigin));
+        assert_eq!(report.module_error, None);
+        assert_eq!(report.error_description(), Some("BadOrigin".to_string()));
+        assert_eq!(report.events.len(), 1);
+        assert_eq!(report.events[0].variant, "Transfer");
+    }
+
+    #[test]
+    fn type_sizes_follow_the_runtime() {
+        #[derive(Clone)]
+        struct SmallRuntime;
+
+        impl Runtime for SmallRuntime {
+            type Index = u32;
+            type BlockNumber = u32;
+            type Hash = crate::Hash;
+            type AccountId = sp_runtime::AccountId32;
+            type Address = sp_runtime::AccountId32;
+            type Balance = u64;
+            type SignedExtra = SmallRuntime;
+        }
+
+        impl crate::extrinsic::xt_primitives::SignedExtra for SmallRuntime {
+            type Index = u32;
+            type Balance = u64;
+            type Hash = crate::Hash;
+            type Custom = ();
+            type Extra = ();
+            type AdditionalSigned = ();
+
+            fn extra(_: &crate::extrinsic::xt_primitives::ExtraParams<u32, u64>) {}
+
+            fn additional_signed(_: &crate::extrinsic::xt_primitives::ExtraParams<u32, u64>) {}
+
+            fn nonce(_: &()) -> u32 {
+                0
+            }
+
+            fn tip(_: &()) -> u64 {
+                0
+            }
+
+            fn era(_: &()) -> sp_runtime::generic::Era {
+                sp_runtime::generic::Era::Immortal
+            }
+        }
+
+        let decoder = EventsDecoder::for_runtime::<SmallRuntime>(metadata()).unwrap();
+
+        assert_eq!(decoder.type_sizes["Balance"], 8);
+        assert_eq!(decoder.type_sizes["BlockNumber"], 4);
+        assert_eq!(EventsDecoder::try_from(metadata()).unwrap().type_sizes["Balance"], 16);
+    }
+
+    #[test]
+    fn report_requires_an_outcome() {
+        let block_events = vec![(Phase::ApplyExtrinsic(0), raw("Transfer"))];
+
+        match ExtrinsicReport::new(crate::Hash::zero(), 0, block_events, &metadata()) {
+            Err(EventsError::MissingExtrinsicOutcome(0)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
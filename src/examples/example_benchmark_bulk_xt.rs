@@ -23,9 +23,7 @@ use keyring::AccountKeyring;
 use node_template_runtime::{BalancesCall, Call};
 use sp_core::crypto::Pair;
 
-use substrate_api_client::{
-    compose_extrinsic_offline, extrinsic::xt_primitives::UncheckedExtrinsicV4, Api, XtStatus
-};
+use substrate_api_client::{extrinsic::xt_primitives::UncheckedExtrinsicV4, Api, XtStatus};
 
 fn main() {
     env_logger::init();
@@ -33,7 +31,11 @@ fn main() {
 
     // initialize api and set the signer (sender) that is used to sign the extrinsics
     let from = AccountKeyring::Alice.pair();
-    let api = Api::new(format!("ws://{}", url)).set_signer(from);
+    let api = Api::new(format!("ws://{}", url))
+        .unwrap()
+        .set_signer(from)
+        .enable_nonce_manager()
+        .unwrap();
 
     println!(
         "[+] Alice's Account Nonce is {}\n",
@@ -43,22 +45,19 @@ fn main() {
     // define the recipient
     let to = AccountKeyring::Bob.to_account_id();
 
-    let mut nonce = api.get_nonce().unwrap();
-
     loop {
-        // compose the extrinsic with all the element
-        let xt: UncheckedExtrinsicV4<_> = compose_extrinsic_offline!(
-            api.clone().signer.unwrap(),
-            Call::Balances(BalancesCall::transfer(to.clone(), 1_000_000)),
-            nonce,
-            api.genesis_hash,
-            api.runtime_version.spec_version
-        );
+        // the nonce manager counts the extrinsics we sent, checks with the node if one is not
+        // accepted
+        let xt: UncheckedExtrinsicV4<_> = api
+            .compose_extrinsic_from_call(Call::Balances(BalancesCall::transfer(to.clone(), 1_000_000)))
+            .unwrap();
+        let nonce = xt.signature.as_ref().unwrap().2.nonce();
+
         // send and watch extrinsic until finalized
         println!("sending extrinsic with nonce {}", nonce);
-        let blockh = api.send_extrinsic(xt.hex_encode(), XtStatus::Ready).unwrap();
-        
-        nonce += 1;
+        if let Err(e) = api.send_extrinsic(xt.hex_encode(), XtStatus::Ready) {
+            println!("extrinsic with nonce {} failed: {}", nonce, e);
+        }
     }
     
 }
@@ -32,7 +32,7 @@ fn main() {
 
     // initialize api and set the signer (sender) that is used to sign the extrinsics
     let from = AccountKeyring::Alice.pair();
-    let api = Api::new(format!("ws://{}", url)).set_signer(from);
+    let api = Api::new(format!("ws://{}", url)).unwrap().set_signer(from);
 
     println!(
         "[+] Alice's Account Nonce is {}\n",
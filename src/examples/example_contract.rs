@@ -46,7 +46,7 @@ fn main() {
 
     // initialize api and set the signer (sender) that is used to sign the extrinsics
     let from = AccountKeyring::Alice.pair();
-    let api = Api::new(format!("ws://{}", url)).set_signer(from);
+    let api = Api::new(format!("ws://{}", url)).unwrap().set_signer(from);
     println!("[+] Alice's Account Nonce is {}", api.get_nonce().unwrap());
 
     // contract to be deployed on the chain
@@ -59,7 +59,7 @@ fn main() {
     let wasm = wabt::wat2wasm(CONTRACT).expect("invalid wabt");
 
     // 1. Put the contract code as a wasm blob on the chain
-    let xt = api.contract_put_code(500_000, wasm);
+    let xt = api.contract_put_code(500_000, wasm).unwrap();
     println!(
         "[+] Putting contract code on chain with extrinsic:\n\n{:?}\n",
         xt
@@ -69,19 +69,18 @@ fn main() {
 
     // setup the events listener for our chain.
     let (events_in, events_out) = channel();
-    api.subscribe_events(events_in.clone());
+    api.subscribe_events(events_in.clone()).unwrap();
 
     // wait for the `contract.CodeStored(code_hash)` event, which returns code hash that is needed
     // to define what contract shall be instantiated afterwards.
     println!("[+] Waiting for the contract.CodeStored event");
     let code_hash: Hash = api
         .wait_for_event("Contract", "CodeStored", &events_out)
-        .unwrap()
         .unwrap();
     println!("[+] Event was received. Got code hash: {:?}\n", code_hash);
 
     // 2. Create an actual instance of the contract
-    let xt = api.contract_instantiate(1_000, 500_000, code_hash, vec![1u8]);
+    let xt = api.contract_instantiate(1_000, 500_000, code_hash, vec![1u8]).unwrap();
 
     println!(
         "[+] Creating a contract instance with extrinsic:\n\n{:?}\n",
@@ -101,7 +100,6 @@ fn main() {
     // Fixme: Somehow no events are thrown from this point. The example hangs here...
     let args: ContractInstantiatedEventArgs = api
         .wait_for_event("Contract", "Instantiated", &events_out)
-        .unwrap()
         .unwrap();
 
     println!(
@@ -110,7 +108,7 @@ fn main() {
     );
 
     // 3. Call the contract instance
-    let xt = api.contract_call(args.deployed_at.into(), 500_000, 500_000, vec![1u8]);
+    let xt = api.contract_call(args.deployed_at.into(), 500_000, 500_000, vec![1u8]).unwrap();
 
     // Currently, a contract call does not fire any events nor interact in any other fashion with
     // the outside world. Only node logs can supply information on the consequences of a contract
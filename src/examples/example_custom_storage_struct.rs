@@ -42,10 +42,10 @@ fn main() {
 
     // initialize api and set the signer (sender) that is used to sign the extrinsics
     let from = AccountKeyring::Alice.pair();
-    let api = Api::new(format!("ws://{}", url)).set_signer(from.clone());
+    let api = Api::new(format!("ws://{}", url)).unwrap().set_signer(from.clone());
 
     let xt: UncheckedExtrinsicV4<_> =
-        compose_extrinsic!(api.clone(), "KittyModule", "create_kitty", 10 as u128);
+        compose_extrinsic!(api.clone(), "KittyModule", "create_kitty", 10 as u128).unwrap();
 
     println!("[+] Composed extrinsic to create Kitty:\n\n {:?}", xt);
 
@@ -57,12 +57,14 @@ fn main() {
     // event similar to what we do in the example_contract.
     let index: u64 = api
         .get_storage_map("Kitty", "KittyIndex", from.public())
+        .unwrap()
         .unwrap();
     println!("[+] Alice's Kitty is at index : {}\n", index);
 
     // get the Kitty
     let kitty: Kitty = api
         .get_storage_map("Kitty", "Kitties", index)
+        .unwrap()
         .unwrap();
     println!("[+] Cute decoded Kitty: {:?}\n", kitty);
 }
@@ -36,15 +36,14 @@ fn main() {
     env_logger::init();
     let url = get_node_url_from_cli();
 
-    let api = Api::<sr25519::Pair>::new(format!("ws://{}", url));
+    let api = Api::<sr25519::Pair>::new(format!("ws://{}", url)).unwrap();
 
     println!("Subscribe to events");
     let (events_in, events_out) = channel();
 
-    api.subscribe_events(events_in.clone());
+    api.subscribe_events(events_in.clone()).unwrap();
     let args: TransferEventArgs = api
         .wait_for_event("Balances", "Transfer", &events_out)
-        .unwrap()
         .unwrap();
 
     println!("Transactor: {:?}", args.from);
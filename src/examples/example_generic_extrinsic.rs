@@ -30,7 +30,7 @@ fn main() {
 
     // initialize api and set the signer (sender) that is used to sign the extrinsics
     let from = AccountKeyring::Alice.pair();
-    let api = Api::new(format!("ws://{}", url)).set_signer(from);
+    let api = Api::new(format!("ws://{}", url)).unwrap().set_signer(from);
 
     // set the recipient
     let to = AccountKeyring::Bob.to_account_id();
@@ -43,7 +43,8 @@ fn main() {
         "transfer",
         GenericAddress::from(to.clone()),
         Compact(42 as u128)
-    );
+    )
+    .unwrap();
 
     println!("[+] Composed Extrinsic:\n {:?}\n", xt);
 
@@ -30,7 +30,7 @@ fn main() {
     env_logger::init();
     let url = get_node_url_from_cli();
 
-    let api = Api::<sr25519::Pair>::new(format!("ws://{}", url));
+    let api = Api::<sr25519::Pair>::new(format!("ws://{}", url)).unwrap();
 
     let head = api.get_finalized_head()
         .map(|h_str| hexstr_to_hash(h_str).unwrap())
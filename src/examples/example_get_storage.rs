@@ -24,22 +24,22 @@ fn main() {
     env_logger::init();
     let url = get_node_url_from_cli();
 
-    let mut api = Api::new(format!("ws://{}", url));
+    let mut api = Api::new(format!("ws://{}", url)).unwrap();
 
     // get some plain storage value
-    let result: u128 = api.get_storage_value("Balances", "TotalIssuance").unwrap();
+    let result: u128 = api.get_storage_value("Balances", "TotalIssuance").unwrap().unwrap();
     println!("[+] TotalIssuance is {}", result);
 
     // get StorageMap
     let accountid = AccountKeyring::Alice.to_account_id();
     let result: Hash = api
-        .get_storage_map("System", "BlockHash", 1u32).or(Some(Hash::default()))
+        .get_storage_map("System", "BlockHash", 1u32).unwrap().or(Some(Hash::default()))
         .unwrap();
     println!("[+] block hash for blocknumber 42 is {:?}", result); 
 
     // get StorageDoubleMap
     let result: u32 = api
-        .get_storage_double_map("TemplateModule", "SomeDoubleMap", 1_u32, 2_u32).or(Some(0))
+        .get_storage_double_map("TemplateModule", "SomeDoubleMap", 1_u32, 2_u32).unwrap().or(Some(0))
         .unwrap();
     println!("[+] some double map (1,2) should be 3. Is {:?}", result); 
 
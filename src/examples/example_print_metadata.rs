@@ -32,9 +32,9 @@ fn main() {
     env_logger::init();
     let url = get_node_url_from_cli();
 
-    let api = Api::<sr25519::Pair>::new(format!("ws://{}", url));
+    let api = Api::<sr25519::Pair>::new(format!("ws://{}", url)).unwrap();
 
-    let meta = Metadata::try_from(api.get_metadata()).unwrap();
+    let meta = Metadata::try_from(api.get_metadata().unwrap()).unwrap();
 
     meta.print_overview();
     meta.print_modules_with_calls();
@@ -43,7 +43,7 @@ fn main() {
     // print full substrate metadata json formatted
     println!(
         "{}",
-        Metadata::pretty_format(&api.get_metadata()).unwrap_or("pretty format failed".to_string())
+        Metadata::pretty_format(&api.get_metadata().unwrap()).unwrap_or("pretty format failed".to_string())
     )
 }
 
@@ -0,0 +1,126 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Signs a transfer with a key the api never sees. A thread stands in for the signing
+//! service, e.g. a hardware security module, that is reached over a local socket.
+
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+
+use clap::{load_yaml, App};
+use codec::{Decode, Encode};
+use keyring::AccountKeyring;
+use sp_core::crypto::Pair;
+use sp_core::sr25519;
+use sp_runtime::{AccountId32, MultiSignature};
+
+use substrate_api_client::{extrinsic::xt_primitives::*, signer::Signer, Api, XtStatus};
+
+/// Asks the signing service at `addr` for the account and the signatures.
+struct RemoteSigner {
+    addr: SocketAddr,
+    account_id: AccountId32,
+}
+
+impl RemoteSigner {
+    fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let account = request(addr, "account")?;
+        let account_id = AccountId32::decode(&mut account.as_slice()).map_err(invalid_data)?;
+        Ok(RemoteSigner { addr, account_id })
+    }
+}
+
+impl Signer for RemoteSigner {
+    type Error = io::Error;
+
+    fn account_id(&self) -> AccountId32 {
+        self.account_id.clone()
+    }
+
+    fn sign(&self, payload: &[u8]) -> io::Result<MultiSignature> {
+        let signature = request(self.addr, &format!("sign {}", hex::encode(payload)))?;
+        MultiSignature::decode(&mut signature.as_slice()).map_err(invalid_data)
+    }
+}
+
+/// Sends one line and reads the hex encoded response line.
+fn request(addr: SocketAddr, line: &str) -> io::Result<Vec<u8>> {
+    let mut stream = TcpStream::connect(addr)?;
+    writeln!(stream, "{}", line)?;
+    let mut response = String::new();
+    BufReader::new(stream).read_line(&mut response)?;
+    hex::decode(response.trim()).map_err(invalid_data)
+}
+
+fn invalid_data<E: std::fmt::Debug>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", error))
+}
+
+/// The signing service. It answers `account` with the encoded account id and `sign <payload>`
+/// with the encoded `MultiSignature` of the hex encoded payload.
+fn serve(listener: TcpListener, pair: sr25519::Pair) {
+    for stream in listener.incoming().filter_map(Result::ok) {
+        let mut line = String::new();
+        if BufReader::new(&stream).read_line(&mut line).is_err() {
+            continue;
+        }
+        let mut words = line.trim().splitn(2, ' ');
+        let response = match (words.next(), words.next()) {
+            (Some("account"), None) => Signer::account_id(&pair).encode(),
+            (Some("sign"), Some(payload)) => match hex::decode(payload) {
+                Ok(payload) => MultiSignature::from(Pair::sign(&pair, &payload)).encode(),
+                Err(_) => continue,
+            },
+            _ => continue,
+        };
+        let _ = writeln!(&stream, "{}", hex::encode(response));
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let url = get_node_url_from_cli();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let signer_addr = listener.local_addr().unwrap();
+    let key = AccountKeyring::Alice.pair();
+    thread::spawn(move || serve(listener, key));
+
+    let signer = RemoteSigner::connect(signer_addr).unwrap();
+    println!("[+] Remote signer at {} signs for {}\n", signer_addr, signer.account_id());
+
+    let api = Api::new(format!("ws://{}", url)).unwrap().set_signer(signer);
+
+    let to = AccountKeyring::Bob.to_account_id();
+    let xt = api.balance_transfer(GenericAddress::from(to), 1000).unwrap();
+    println!("[+] Composed extrinsic: {:?}\n", xt);
+
+    let tx_hash = api.send_extrinsic(xt.hex_encode(), XtStatus::InBlock).unwrap();
+    println!("[+] Transaction got included. Hash: {:?}\n", tx_hash);
+}
+
+pub fn get_node_url_from_cli() -> String {
+    let yml = load_yaml!("../../src/examples/cli.yml");
+    let matches = App::from_yaml(yml).get_matches();
+
+    let node_ip = matches.value_of("node-server").unwrap_or("127.0.0.1");
+    let node_port = matches.value_of("node-port").unwrap_or("9944");
+    let url = format!("{}:{}", node_ip, node_port);
+    println!("Interacting with node on {}\n", url);
+    url
+}
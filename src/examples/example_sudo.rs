@@ -32,7 +32,7 @@ fn main() {
 
     // initialize api and set the signer (sender) that is used to sign the extrinsics
     let sudoer = AccountKeyring::Alice.pair();
-    let api = Api::new(format!("ws://{}", url)).set_signer(sudoer.clone());
+    let api = Api::new(format!("ws://{}", url)).unwrap().set_signer(sudoer.clone());
 
     // set the recipient of newly issued funds
     let to = AccountKeyring::Bob.to_account_id();
@@ -45,8 +45,9 @@ fn main() {
         GenericAddress::from(to.clone()),
         Compact(42 as u128),
         Compact(42 as u128)
-    );
-    let xt: UncheckedExtrinsicV4<_> = compose_extrinsic!(api.clone(), "Sudo", "sudo", call);
+    )
+    .unwrap();
+    let xt: UncheckedExtrinsicV4<_> = compose_extrinsic!(api.clone(), "Sudo", "sudo", call).unwrap();
 
     // send and watch extrinsic until finalized
     let tx_hash = api.send_extrinsic(xt.hex_encode(), XtStatus::Finalized).unwrap();
@@ -26,16 +26,16 @@ fn main() {
 
     // initialize api and set the signer (sender) that is used to sign the extrinsics
     let from = AccountKeyring::Alice.pair();
-    let api = Api::new(format!("ws://{}", url)).set_signer(from.clone());
+    let api = Api::new(format!("ws://{}", url)).unwrap().set_signer(from.clone());
 
     let to = AccountKeyring::Bob.to_account_id();
 
-    match api.get_account_data(&to) {
+    match api.get_account_data(&to).unwrap() {
         Some(bob) => println!("[+] Bob's Free Balance is is {}\n", bob.free),
         None => println!("[+] Bob's Free Balance is is {}\n", 0),
     }
     // generate extrinsic
-    let xt = api.balance_transfer(GenericAddress::from(to.clone()), 1000);
+    let xt = api.balance_transfer(GenericAddress::from(to.clone()), 1000).unwrap();
 
     println!(
         "Sending an extrinsic from Alice (Key = {:?}),\n\nto Bob (Key = {:?})\n",
@@ -50,7 +50,7 @@ fn main() {
     println!("[+] Transaction got finalized. Hash: {:?}\n", tx_hash);
 
     // verify that Bob's free Balance increased
-    let bob = api.get_account_data(&to).unwrap();
+    let bob = api.get_account_data(&to).unwrap().unwrap();
     println!("[+] Bob's Free Balance is now {}\n", bob.free);
 }
 
@@ -15,13 +15,12 @@
 
 */
 
-use codec::Compact;
+use codec::{Compact, Encode};
 
 use super::xt_primitives::*;
 #[cfg(feature = "std")]
-use crate::{compose_extrinsic, Api};
-use sp_core::crypto::Pair;
-use sp_runtime::MultiSignature;
+use crate::{compose_extrinsic, rpc::RpcTransport, signer::Signer, Api, ApiResult};
+use crate::runtime::{DefaultRuntime, Runtime};
 
 pub const BALANCES_MODULE: &str = "Balances";
 pub const BALANCES_TRANSFER: &str = "transfer";
@@ -30,24 +29,31 @@ pub const BALANCES_SET_BALANCE: &str = "set_balance";
 pub type CallIndex = [u8; 2];
 pub type Balance = u128;
 
-pub type BalanceTransferFn = (CallIndex, GenericAddress, Compact<Balance>);
-pub type BalanceSetBalanceFn = (
+pub type BalanceTransferFn<R = DefaultRuntime> = (
     CallIndex,
-    GenericAddress,
-    Compact<Balance>,
-    Compact<Balance>,
+    <R as Runtime>::Address,
+    Compact<<R as Runtime>::Balance>,
+);
+pub type BalanceSetBalanceFn<R = DefaultRuntime> = (
+    CallIndex,
+    <R as Runtime>::Address,
+    Compact<<R as Runtime>::Balance>,
+    Compact<<R as Runtime>::Balance>,
 );
 
-pub type BalanceTransferXt = UncheckedExtrinsicV4<BalanceTransferFn>;
-pub type BalanceSetBalanceXt = UncheckedExtrinsicV4<BalanceSetBalanceFn>;
+pub type BalanceTransferXt<R = DefaultRuntime> = UncheckedExtrinsicV4<BalanceTransferFn<R>, R>;
+pub type BalanceSetBalanceXt<R = DefaultRuntime> =
+    UncheckedExtrinsicV4<BalanceSetBalanceFn<R>, R>;
 
 #[cfg(feature = "std")]
-impl<P> Api<P>
+impl<P, Client, R> Api<P, Client, R>
 where
-    P: Pair,
-    MultiSignature: From<P::Signature>,
+    P: Signer<R::AccountId>,
+    Client: RpcTransport,
+    R: Runtime,
+    Compact<R::Balance>: Encode + Clone,
 {
-    pub fn balance_transfer(&self, to: GenericAddress, amount: Balance) -> BalanceTransferXt {
+    pub fn balance_transfer(&self, to: R::Address, amount: R::Balance) -> ApiResult<BalanceTransferXt<R>> {
         compose_extrinsic!(
             self,
             BALANCES_MODULE,
@@ -59,10 +65,10 @@ where
 
     pub fn balance_set_balance(
         &self,
-        who: GenericAddress,
-        free_balance: Balance,
-        reserved_balance: Balance,
-    ) -> BalanceSetBalanceXt {
+        who: R::Address,
+        free_balance: R::Balance,
+        reserved_balance: R::Balance,
+    ) -> ApiResult<BalanceSetBalanceXt<R>> {
         compose_extrinsic!(
             self,
             BALANCES_MODULE,
@@ -15,16 +15,15 @@
 
 */
 
-use codec::Compact;
-use sp_core::crypto::Pair;
+use codec::{Compact, Encode};
 use sp_core::H256 as Hash;
-use sp_runtime::MultiSignature;
 use sp_std::prelude::*;
 
 #[cfg(feature = "std")]
-use crate::{compose_extrinsic, Api};
+use crate::{compose_extrinsic, rpc::RpcTransport, signer::Signer, Api, ApiResult};
 
 use super::xt_primitives::*;
+use crate::runtime::{DefaultRuntime, Runtime};
 
 pub const CONTRACTS_MODULE: &str = "Contract";
 pub const CONTRACTS_PUT_CODE: &str = "put_code";
@@ -35,28 +34,32 @@ type CallIndex = [u8; 2];
 
 type Gas = u64;
 type Data = Vec<u8>;
-type Balance = u128;
 
 type GasLimit = Compact<Gas>;
-type Endowment = Compact<Balance>;
-type Value = Compact<Balance>;
-type Destination = GenericAddress;
+type Endowment<R> = Compact<<R as Runtime>::Balance>;
+type Value<R> = Compact<<R as Runtime>::Balance>;
+type Destination<R> = <R as Runtime>::Address;
 
 pub type ContractPutCodeFn = (CallIndex, GasLimit, Data);
-pub type ContractInstantiateFn = (CallIndex, Endowment, GasLimit, Hash, Data);
-pub type ContractCallFn = (CallIndex, Destination, Value, GasLimit, Data);
+pub type ContractInstantiateFn<R = DefaultRuntime> =
+    (CallIndex, Endowment<R>, GasLimit, Hash, Data);
+pub type ContractCallFn<R = DefaultRuntime> =
+    (CallIndex, Destination<R>, Value<R>, GasLimit, Data);
 
-pub type ContractPutCodeXt = UncheckedExtrinsicV4<ContractPutCodeFn>;
-pub type ContractInstantiateXt = UncheckedExtrinsicV4<ContractInstantiateFn>;
-pub type ContractCallXt = UncheckedExtrinsicV4<ContractCallFn>;
+pub type ContractPutCodeXt<R = DefaultRuntime> = UncheckedExtrinsicV4<ContractPutCodeFn, R>;
+pub type ContractInstantiateXt<R = DefaultRuntime> =
+    UncheckedExtrinsicV4<ContractInstantiateFn<R>, R>;
+pub type ContractCallXt<R = DefaultRuntime> = UncheckedExtrinsicV4<ContractCallFn<R>, R>;
 
 #[cfg(feature = "std")]
-impl<P> Api<P>
+impl<P, Client, R> Api<P, Client, R>
 where
-    P: Pair,
-    MultiSignature: From<P::Signature>,
+    P: Signer<R::AccountId>,
+    Client: RpcTransport,
+    R: Runtime,
+    Compact<R::Balance>: Encode + Clone,
 {
-    pub fn contract_put_code(&self, gas_limit: Gas, code: Data) -> ContractPutCodeXt {
+    pub fn contract_put_code(&self, gas_limit: Gas, code: Data) -> ApiResult<ContractPutCodeXt<R>> {
         compose_extrinsic!(
             &self,
             CONTRACTS_MODULE,
@@ -68,11 +71,11 @@ where
 
     pub fn contract_instantiate(
         &self,
-        endowment: Balance,
+        endowment: R::Balance,
         gas_limit: Gas,
         code_hash: Hash,
         data: Data,
-    ) -> ContractInstantiateXt {
+    ) -> ApiResult<ContractInstantiateXt<R>> {
         compose_extrinsic!(
             self,
             CONTRACTS_MODULE,
@@ -86,11 +89,11 @@ where
 
     pub fn contract_call(
         &self,
-        dest: GenericAddress,
-        value: Balance,
+        dest: R::Address,
+        value: R::Balance,
         gas_limit: Gas,
         data: Data,
-    ) -> ContractCallXt {
+    ) -> ApiResult<ContractCallXt<R>> {
         compose_extrinsic!(
             self,
             CONTRACTS_MODULE,
@@ -29,7 +29,8 @@ pub mod balances;
 pub mod contract;
 pub mod xt_primitives;
 
-/// Generates the extrinsic's call field for a given module and call passed as &str
+/// Generates the extrinsic's call field for a given module and call passed as &str. Returns the
+/// `MetadataError` if the module or call is not in the metadata.
 /// # Arguments
 ///
 /// * 'node_metadata' - This crate's parsed node metadata as field of the API.
@@ -41,11 +42,9 @@ pub mod xt_primitives;
 macro_rules! compose_call {
 ($node_metadata: expr, $module: expr, $call_name: expr $(, $args: expr) *) => {
         {
-            let module = $node_metadata.module_with_calls($module).unwrap().to_owned();
-
-            let call_index = module.calls.get($call_name).unwrap();
-
-            ([module.index, *call_index as u8] $(, ($args)) *)
+            $node_metadata
+                .call_index($module, $call_name)
+                .map(|call_index| (call_index $(, ($args)) *))
         }
     };
 }
@@ -53,11 +52,22 @@ macro_rules! compose_call {
 /// Generates an Unchecked extrinsic for a given call
 /// # Arguments
 ///
-/// * 'signer' - AccountKey that is used to sign the extrinsic.
+/// * 'signer' - `signer::Signer` of the extrinsic, e.g. an sr25519, ed25519 or ecdsa key pair.
 /// * 'call' - call as returned by the compose_call! macro or via substrate's call enums.
 /// * 'nonce' - signer's account nonce: u32
 /// * 'genesis_hash' - sp-runtime::Hash256/[u8; 32].
 /// * 'runtime_spec_version' - RuntimeVersion.spec_version/u32
+/// * 'era' - Optional sp-runtime::generic::Era, immortal if omitted.
+/// * 'era_checkpoint' - Hash of the block the era starts at, `era.birth(current_block)`. The
+/// genesis hash for an immortal era. See `Api::get_era`.
+/// * 'tip' - Optional tip for the block author: u128, 0 if omitted. Requires the era.
+///
+/// The extrinsic is composed for the substrate node, `DefaultRuntime`. For other runtimes, pass
+/// `ExtraParams` and the `Runtime` implementation instead:
+/// `compose_extrinsic_offline!(signer, call, params => MyRuntime)`.
+///
+/// Panics if the signer fails, use `signer::sign_extrinsic` to handle the error of a remote
+/// signer.
 #[macro_export]
 macro_rules! compose_extrinsic_offline {
     ($signer: expr,
@@ -65,38 +75,69 @@ macro_rules! compose_extrinsic_offline {
     $nonce: expr,
     $genesis_hash: expr,
     $runtime_spec_version: expr) => {{
-        use $crate::extrinsic::xt_primitives::*;
-
-        let extra = GenericExtra::new($nonce);
-        let raw_payload = SignedPayload::from_raw(
-            $call.clone(),
-            extra.clone(),
-            (
-                $runtime_spec_version,
-                $genesis_hash,
-                $genesis_hash,
-                (),
-                (),
-                (),
-                (),
-            ),
-        );
-
-        let signature = raw_payload.using_encoded(|payload| $signer.sign(payload));
-
-        let mut arr: [u8; 32] = Default::default();
-        arr.clone_from_slice($signer.public().as_ref());
-
-        UncheckedExtrinsicV4::new_signed(
+        $crate::compose_extrinsic_offline!(
+            $signer,
             $call,
-            GenericAddress::from(AccountId::from(arr)),
-            signature.into(),
-            extra,
+            $nonce,
+            $genesis_hash,
+            $runtime_spec_version,
+            $crate::extrinsic::xt_primitives::Era::Immortal,
+            $genesis_hash
         )
     }};
+    ($signer: expr,
+    $call: expr,
+    $nonce: expr,
+    $genesis_hash: expr,
+    $runtime_spec_version: expr,
+    $era: expr,
+    $era_checkpoint: expr) => {{
+        $crate::compose_extrinsic_offline!(
+            $signer,
+            $call,
+            $nonce,
+            $genesis_hash,
+            $runtime_spec_version,
+            $era,
+            $era_checkpoint,
+            0
+        )
+    }};
+    ($signer: expr,
+    $call: expr,
+    $nonce: expr,
+    $genesis_hash: expr,
+    $runtime_spec_version: expr,
+    $era: expr,
+    $era_checkpoint: expr,
+    $tip: expr) => {{
+        $crate::compose_extrinsic_offline!(
+            $signer,
+            $call,
+            $crate::extrinsic::xt_primitives::ExtraParams {
+                nonce: $nonce,
+                tip: $tip,
+                era: $era,
+                era_checkpoint: $era_checkpoint,
+                genesis_hash: $genesis_hash,
+                spec_version: $runtime_spec_version,
+                custom: (),
+            } => $crate::runtime::DefaultRuntime
+        )
+    }};
+    ($signer: expr,
+    $call: expr,
+    $params: expr => $runtime: ty) => {{
+        match $crate::signer::sign_extrinsic::<_, $runtime, _>(&$signer, $call, &$params) {
+            Ok(xt) => xt,
+            Err(e) => panic!("Signing the extrinsic failed: {:?}", e),
+        }
+    }};
 }
 
-/// Generates an Unchecked extrinsic for a given module and call passed as a &str.
+/// Generates an Unchecked extrinsic for a given module and call passed as a &str. Returns the
+/// `ApiError` if the module or call is not in the metadata, the nonce or era could not be fetched
+/// or the signer failed.
 /// # Arguments
 ///
 /// * 'api' - This instance of API. If the *signer* field is not set, an unsigned extrinsic will be generated.
@@ -115,24 +156,11 @@ macro_rules! compose_extrinsic {
 		{
             use $crate::extrinsic::codec::Compact;
             use $crate::extrinsic::log::info;
-            use $crate::extrinsic::xt_primitives::*;
 
             info!("Composing generic extrinsic for module {:?} and call {:?}", $module, $call);
-            let call = $crate::compose_call!($api.metadata.clone(), $module, $call $(, ($args)) *);
-
-            if let Some(signer) = $api.signer.clone() {
-                $crate::compose_extrinsic_offline!(
-                    signer,
-                    call.clone(),
-                    $api.get_nonce().unwrap(),
-                    $api.genesis_hash,
-                    $api.runtime_version.spec_version
-                )
-            } else {
-                UncheckedExtrinsicV4 {
-                    signature: None,
-                    function: call.clone(),
-                }
+            match $crate::compose_call!($api.metadata, $module, $call $(, ($args)) *) {
+                Ok(call) => $api.compose_extrinsic_from_call(call),
+                Err(e) => Err($crate::ApiError::from(e)),
             }
 		}
     };
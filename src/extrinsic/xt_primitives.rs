@@ -20,17 +20,46 @@ use sp_std::prelude::*;
 #[cfg(feature = "std")]
 use std::fmt;
 
-use codec::{Compact, Decode, Encode};
-//use indices::address::Address;
+use codec::{Compact, Decode, Encode, HasCompact, Output};
 use sp_core::H256;
-use sp_core::blake2_256;
-use sp_runtime::{generic::Era, MultiSignature};
+use sp_core::{blake2_256, RuntimeDebug};
+use sp_runtime::MultiSignature;
 
+use crate::runtime::{DefaultRuntime, Runtime};
+
+pub use sp_runtime::generic::Era;
 pub use sp_runtime::AccountId32 as AccountId;
 
-pub type AccountIndex = u64;
+/// Index of an account in the indices pallet, matches the node's `AccountIndex`, `u32`.
+pub type AccountIndex = u32;
+
+/// Address of runtimes whose `Lookup` is `IdentityLookup`, like the substrate node's.
+pub type GenericAddress = AccountId;
+
+/// Address of runtimes whose `Lookup` is `pallet_indices`: an account id or an account index.
+pub type IndicesAddress = indices::address::Address<AccountId, AccountIndex>;
 
-pub type GenericAddress = AccountId; //Address<AccountId, AccountIndex>;
+/// Address of runtimes whose `Lookup` resolves a `MultiAddress`. Mirrors
+/// `sp_runtime::MultiAddress` of newer substrate versions.
+#[derive(Encode, Decode, Clone, Eq, PartialEq, RuntimeDebug)]
+pub enum MultiAddress<AccountId, AccountIndex: HasCompact> {
+    /// An account id
+    Id(AccountId),
+    /// An account index, resolved by the indices pallet
+    Index(#[codec(compact)] AccountIndex),
+    /// Raw bytes, e.g. of an unknown kind of address
+    Raw(Vec<u8>),
+    /// A 32 byte address, e.g. a hash
+    Address32([u8; 32]),
+    /// A 20 byte address, e.g. an ethereum address
+    Address20([u8; 20]),
+}
+
+impl<AccountId, AccountIndex: HasCompact> From<AccountId> for MultiAddress<AccountId, AccountIndex> {
+    fn from(account_id: AccountId) -> Self {
+        MultiAddress::Id(account_id)
+    }
+}
 
 /// Simple generic extra mirroring the SignedExtra currently used in extrinsics. Does not implement
 /// the SignedExtension trait. It simply encodes to the same bytes as the real SignedExtra. The
@@ -42,8 +71,34 @@ pub type GenericAddress = AccountId; //Address<AccountId, AccountIndex>;
 pub struct GenericExtra(Era, Compact<u32>, Compact<u128>);
 
 impl GenericExtra {
+    /// Extra of an immortal transaction.
     pub fn new(nonce: u32) -> GenericExtra {
-        GenericExtra(Era::Immortal, Compact(nonce), Compact(0 as u128))
+        GenericExtra::new_with_era(Era::Immortal, nonce)
+    }
+
+    /// Extra of a transaction valid during `era`, e.g. `Era::mortal(period, current_block)`.
+    /// The hash of the block `era.birth(current_block)` must be signed as the era checkpoint.
+    pub fn new_with_era(era: Era, nonce: u32) -> GenericExtra {
+        GenericExtra(era, Compact(nonce), Compact(0 as u128))
+    }
+
+    /// Pays `tip` on top of the fees to the block author, which raises the priority of the
+    /// transaction in the pool.
+    pub fn set_tip(mut self, tip: u128) -> GenericExtra {
+        self.2 = Compact(tip);
+        self
+    }
+
+    pub fn era(&self) -> Era {
+        self.0
+    }
+
+    pub fn nonce(&self) -> u32 {
+        (self.1).0
+    }
+
+    pub fn tip(&self) -> u128 {
+        (self.2).0
     }
 }
 
@@ -51,14 +106,117 @@ impl GenericExtra {
 /// Order is the same as declared in the extra.
 pub type AdditionalSigned = (u32, H256, H256, (), (), (), ());
 
-#[derive(Encode, Clone)]
-pub struct SignedPayload<Call>((Call, GenericExtra, AdditionalSigned));
+/// Everything the signed extensions of a runtime may be built from.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Eq, PartialEq)]
+pub struct ExtraParams<Index = u32, Balance = u128, Hash = H256, Custom = ()> {
+    pub nonce: Index,
+    pub tip: Balance,
+    pub era: Era,
+    /// Hash of the block `era` starts at, the genesis hash for an immortal era
+    pub era_checkpoint: Hash,
+    pub genesis_hash: Hash,
+    pub spec_version: u32,
+    /// Inputs of the runtime's own extensions, see `SignedExtra::Custom`
+    pub custom: Custom,
+}
+
+/// The `ExtraParams` of the signed extensions `S`.
+pub type ExtraParamsOf<S> = ExtraParams<
+    <S as SignedExtra>::Index,
+    <S as SignedExtra>::Balance,
+    <S as SignedExtra>::Hash,
+    <S as SignedExtra>::Custom,
+>;
+
+/// The signed extensions of a runtime, i.e. its `SignedExtra` tuple. Does not implement the
+/// SignedExtension trait, it only has to encode to the same bytes.
+pub trait SignedExtra {
+    type Index;
+    type Balance;
+    type Hash;
+    /// Inputs of extensions beyond nonce, tip, era and versions, e.g. the asset to pay the fees
+    /// with. `()` if there are none. See `Api::set_custom_extra_params`.
+    type Custom: Clone + Default;
+    /// Encoded into every signed extrinsic, e.g. era, nonce and tip.
+    type Extra: Encode + Clone;
+    /// Not part of the extrinsic, but signed along, e.g. the genesis hash.
+    type AdditionalSigned: Encode + Clone;
+
+    fn extra(params: &ExtraParamsOf<Self>) -> Self::Extra;
+
+    fn additional_signed(params: &ExtraParamsOf<Self>) -> Self::AdditionalSigned;
+
+    /// The nonce `extra` was built from. Along with the tip and era, it is read back to replace
+    /// an extrinsic, see `Api::compose_replacement`.
+    fn nonce(extra: &Self::Extra) -> Self::Index;
 
-impl<Call> SignedPayload<Call>
+    fn tip(extra: &Self::Extra) -> Self::Balance;
+
+    fn era(extra: &Self::Extra) -> Era;
+}
+
+/// The signed extensions of the substrate node, see `GenericExtra` and `AdditionalSigned`.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Eq, PartialEq)]
+pub struct DefaultSignedExtra;
+
+impl SignedExtra for DefaultSignedExtra {
+    type Index = u32;
+    type Balance = u128;
+    type Hash = H256;
+    type Custom = ();
+    type Extra = GenericExtra;
+    type AdditionalSigned = AdditionalSigned;
+
+    fn extra(params: &ExtraParams) -> GenericExtra {
+        GenericExtra::new_with_era(params.era, params.nonce).set_tip(params.tip)
+    }
+
+    fn additional_signed(params: &ExtraParams) -> AdditionalSigned {
+        (
+            params.spec_version,
+            params.genesis_hash,
+            params.era_checkpoint,
+            (),
+            (),
+            (),
+            (),
+        )
+    }
+
+    fn nonce(extra: &GenericExtra) -> u32 {
+        extra.nonce()
+    }
+
+    fn tip(extra: &GenericExtra) -> u128 {
+        extra.tip()
+    }
+
+    fn era(extra: &GenericExtra) -> Era {
+        extra.era()
+    }
+}
+
+#[derive(Clone)]
+pub struct SignedPayload<Call, S: SignedExtra = DefaultSignedExtra>(
+    (Call, S::Extra, S::AdditionalSigned),
+);
+
+impl<Call, S> SignedPayload<Call, S>
 where
     Call: Encode,
+    S: SignedExtra,
 {
-    pub fn from_raw(call: Call, extra: GenericExtra, additional_signed: AdditionalSigned) -> Self {
+    pub fn new(call: Call, params: &ExtraParamsOf<S>) -> Self {
+        Self((call, S::extra(params), S::additional_signed(params)))
+    }
+
+    pub fn from_raw(
+        call: Call,
+        extra: S::Extra,
+        additional_signed: S::AdditionalSigned,
+    ) -> Self {
         Self((call, extra, additional_signed))
     }
 
@@ -76,26 +234,41 @@ where
     }
 }
 
+impl<Call, S> Encode for SignedPayload<Call, S>
+where
+    Call: Encode,
+    S: SignedExtra,
+{
+    fn encode_to<T: Output>(&self, dest: &mut T) {
+        self.0.encode_to(dest)
+    }
+}
+
 /// Mirrors the currently used Extrinsic format (V3) from substrate. Has less traits and methods though.
 /// The SingedExtra used does not need to implement SingedExtension here.
 #[derive(Clone)]
-pub struct UncheckedExtrinsicV4<Call>
+pub struct UncheckedExtrinsicV4<Call, R = DefaultRuntime>
 where
     Call: Encode,
+    R: Runtime,
 {
-    pub signature: Option<(GenericAddress, MultiSignature, GenericExtra)>,
+    pub signature: Option<(R::Address, MultiSignature, SignedExtraOf<R>)>,
     pub function: Call,
 }
 
-impl<Call> UncheckedExtrinsicV4<Call>
+/// The extra encoded into the signed extrinsics of runtime `R`.
+pub type SignedExtraOf<R> = <<R as Runtime>::SignedExtra as SignedExtra>::Extra;
+
+impl<Call, R> UncheckedExtrinsicV4<Call, R>
 where
     Call: Encode,
+    R: Runtime,
 {
     pub fn new_signed(
         function: Call,
-        signed: GenericAddress,
+        signed: R::Address,
         signature: MultiSignature,
-        extra: GenericExtra,
+        extra: SignedExtraOf<R>,
     ) -> Self {
         UncheckedExtrinsicV4 {
             signature: Some((signed, signature, extra)),
@@ -112,9 +285,11 @@ where
 }
 
 #[cfg(feature = "std")]
-impl<Call> fmt::Debug for UncheckedExtrinsicV4<Call>
+impl<Call, R> fmt::Debug for UncheckedExtrinsicV4<Call, R>
 where
     Call: fmt::Debug + Encode,
+    R: Runtime,
+    SignedExtraOf<R>: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -126,9 +301,10 @@ where
     }
 }
 
-impl<Call> Encode for UncheckedExtrinsicV4<Call>
+impl<Call, R> Encode for UncheckedExtrinsicV4<Call, R>
 where
     Call: Encode,
+    R: Runtime,
 {
     fn encode(&self) -> Vec<u8> {
         encode_with_vec_prefix::<Self, _>(|v| {
@@ -168,3 +344,225 @@ fn encode_with_vec_prefix<T: Encode, F: Fn(&mut Vec<u8>)>(encoder: F) -> Vec<u8>
 
     v
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_core::sr25519;
+
+    /// Extensions of a node with an additional `CheckTxVersion`, which signs the transaction
+    /// version along, and a `ChargeAssetTxPayment`, which pays the fees in an optional asset.
+    #[derive(Clone)]
+    struct AssetSignedExtra;
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct AssetParams {
+        asset_id: Option<u32>,
+        tx_version: u32,
+    }
+
+    #[derive(Clone, Debug, Decode, Encode, PartialEq)]
+    struct AssetExtra {
+        era: Era,
+        nonce: Compact<u32>,
+        tip: Compact<u128>,
+        asset_id: Option<u32>,
+    }
+
+    #[derive(Clone)]
+    struct AssetRuntime;
+
+    impl Runtime for AssetRuntime {
+        type Index = u32;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type AccountId = AccountId;
+        type Address = AccountId;
+        type Balance = u128;
+        type SignedExtra = AssetSignedExtra;
+    }
+
+    impl SignedExtra for AssetSignedExtra {
+        type Index = u32;
+        type Balance = u128;
+        type Hash = H256;
+        type Custom = AssetParams;
+        type Extra = AssetExtra;
+        type AdditionalSigned = (u32, u32, H256, H256, (), (), (), ());
+
+        fn extra(params: &ExtraParamsOf<Self>) -> AssetExtra {
+            AssetExtra {
+                era: params.era,
+                nonce: Compact(params.nonce),
+                tip: Compact(params.tip),
+                asset_id: params.custom.asset_id,
+            }
+        }
+
+        fn additional_signed(params: &ExtraParamsOf<Self>) -> Self::AdditionalSigned {
+            (
+                params.spec_version,
+                params.custom.tx_version,
+                params.genesis_hash,
+                params.era_checkpoint,
+                (),
+                (),
+                (),
+                (),
+            )
+        }
+
+        fn nonce(extra: &AssetExtra) -> u32 {
+            extra.nonce.0
+        }
+
+        fn tip(extra: &AssetExtra) -> u128 {
+            extra.tip.0
+        }
+
+        fn era(extra: &AssetExtra) -> Era {
+            extra.era
+        }
+    }
+
+    #[test]
+    fn additional_signed_is_signed_but_not_encoded_in_extrinsic() {
+        let params = ExtraParams {
+            nonce: 3,
+            tip: 5,
+            era: Era::Immortal,
+            era_checkpoint: H256::repeat_byte(1),
+            genesis_hash: H256::repeat_byte(1),
+            spec_version: 1,
+            custom: (),
+        };
+        let asset_params = ExtraParams {
+            nonce: 3,
+            tip: 5,
+            era: Era::Immortal,
+            era_checkpoint: H256::repeat_byte(1),
+            genesis_hash: H256::repeat_byte(1),
+            spec_version: 1,
+            custom: AssetParams {
+                asset_id: Some(9),
+                tx_version: 2,
+            },
+        };
+        let extra = GenericExtra::new(3).set_tip(5);
+        let asset_extra = AssetSignedExtra::extra(&asset_params);
+        let default_payload = SignedPayload::<_, DefaultSignedExtra>::new(vec![7u8], &params);
+        let asset_payload = SignedPayload::<_, AssetSignedExtra>::new(vec![7u8], &asset_params);
+
+        assert_eq!(
+            default_payload.encode(),
+            (vec![7u8], &extra, (1u32, params.genesis_hash, params.genesis_hash, (), (), (), ()))
+                .encode()
+        );
+        assert_eq!(
+            asset_payload.encode(),
+            (vec![7u8], &extra, Some(9u32), (1u32, 2u32, params.genesis_hash, params.genesis_hash))
+                .encode()
+        );
+
+        let signature = MultiSignature::from(sr25519::Signature::default());
+        let default_xt = UncheckedExtrinsicV4::<_, DefaultRuntime>::new_signed(
+            vec![7u8],
+            AccountId::from([0u8; 32]),
+            signature.clone(),
+            extra.clone(),
+        );
+        let asset_xt = UncheckedExtrinsicV4::<_, AssetRuntime>::new_signed(
+            vec![7u8],
+            AccountId::from([0u8; 32]),
+            signature,
+            asset_extra,
+        );
+        let default_encoded = default_xt.encode();
+        let asset_encoded = asset_xt.encode();
+        // both have a two byte length, the asset id follows the extra of the default extensions
+        let extra_end = default_encoded.len() - vec![7u8].encode().len();
+        assert_eq!(asset_encoded[2..extra_end], default_encoded[2..extra_end]);
+        assert_eq!(asset_encoded[extra_end..extra_end + 5], Some(9u32).encode()[..]);
+        assert_eq!(asset_encoded[extra_end + 5..], vec![7u8].encode()[..]);
+    }
+
+    #[test]
+    fn nonce_tip_and_era_are_read_back_from_the_extra() {
+        let era = Era::mortal(64, 100);
+        let params = ExtraParams {
+            nonce: 3,
+            tip: 5,
+            era,
+            era_checkpoint: H256::repeat_byte(2),
+            genesis_hash: H256::repeat_byte(1),
+            spec_version: 1,
+            custom: AssetParams {
+                asset_id: Some(9),
+                tx_version: 2,
+            },
+        };
+        let extra = AssetSignedExtra::extra(&params);
+
+        assert_eq!(AssetSignedExtra::nonce(&extra), 3);
+        assert_eq!(AssetSignedExtra::tip(&extra), 5);
+        assert_eq!(AssetSignedExtra::era(&extra), era);
+    }
+
+    #[derive(Clone)]
+    struct IndicesRuntime;
+
+    impl Runtime for IndicesRuntime {
+        type Index = u32;
+        type BlockNumber = u64;
+        type Hash = H256;
+        type AccountId = AccountId;
+        type Address = MultiAddress<AccountId, AccountIndex>;
+        type Balance = u128;
+        type SignedExtra = DefaultSignedExtra;
+    }
+
+    #[test]
+    fn indices_address_encodes_like_substrate() {
+        let account = AccountId::from([2u8; 32]);
+
+        let mut expected = vec![0xffu8];
+        expected.extend_from_slice(&[2u8; 32]);
+        assert_eq!(IndicesAddress::from(account.clone()).encode(), expected);
+        assert_eq!(IndicesAddress::Index(5).encode(), vec![5]);
+        assert_eq!(IndicesAddress::Index(0x1234).encode(), vec![0xfc, 0x34, 0x12]);
+        assert_eq!(
+            IndicesAddress::Index(0x0012_3456).encode(),
+            vec![0xfd, 0x56, 0x34, 0x12, 0x00]
+        );
+        assert_eq!(
+            IndicesAddress::decode(&mut &expected[..]).unwrap(),
+            IndicesAddress::Id(account)
+        );
+    }
+
+    #[test]
+    fn multi_address_encodes_like_substrate() {
+        let account = AccountId::from([2u8; 32]);
+
+        let mut expected = vec![0u8];
+        expected.extend_from_slice(&[2u8; 32]);
+        assert_eq!(MultiAddress::<_, AccountIndex>::Id(account.clone()).encode(), expected);
+        assert_eq!(MultiAddress::<AccountId, AccountIndex>::Index(5).encode(), vec![1, 5 << 2]);
+
+        let xt = UncheckedExtrinsicV4::<_, IndicesRuntime>::new_signed(
+            vec![7u8],
+            account.clone().into(),
+            MultiSignature::from(sr25519::Signature::default()),
+            GenericExtra::new(0),
+        );
+        let plain_xt = UncheckedExtrinsicV4::<_, DefaultRuntime>::new_signed(
+            vec![7u8],
+            account,
+            MultiSignature::from(sr25519::Signature::default()),
+            GenericExtra::new(0),
+        );
+        // after the two byte length and the version, the signer has one more byte for the variant
+        assert_eq!(xt.encode()[3], 0);
+        assert_eq!(xt.encode()[4..], plain_xt.encode()[3..]);
+    }
+}
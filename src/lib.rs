@@ -23,17 +23,24 @@ use sp_std::prelude::*;
 #[cfg(feature = "std")]
 use std::sync::mpsc::Sender as ThreadOut;
 #[cfg(feature = "std")]
-use std::sync::mpsc::{channel, Receiver};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+
+#[cfg(feature = "std")]
+use serde::{de::DeserializeOwned, Deserialize};
+#[cfg(feature = "std")]
+use serde_json::Value;
 
 #[cfg(feature = "std")]
 use std::convert::TryFrom;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "std")]
 use balances::AccountData as AccountDataGen;
 #[cfg(feature = "std")]
 use system::AccountInfo as AccountInfoGen;
 #[cfg(feature = "std")]
-use codec::{Decode, Encode, Error as CodecError};
+use codec::{Compact, Decode, DecodeAll, Encode, Error as CodecError};
 
 #[cfg(feature = "std")]
 use log::{debug, error, info};
@@ -41,203 +48,478 @@ use log::{debug, error, info};
 #[cfg(feature = "std")]
 use metadata::RuntimeMetadataPrefixed;
 #[cfg(feature = "std")]
-use sp_core::crypto::Pair;
-
-#[cfg(feature = "std")]
-use ws::Result as WsResult;
-
-#[cfg(feature = "std")]
-use node_metadata::Metadata;
+use node_metadata::{Metadata, MetadataError};
 
 #[cfg(feature = "std")]
-use rpc::json_req;
+use rpc::{
+    json_req, FailoverRpcClient, JsonRpcError, RpcError, RpcTransport, WsRpcClient,
+    DEFAULT_REQUEST_TIMEOUT,
+};
 
 #[cfg(feature = "std")]
 use utils::*;
 
 #[cfg(feature = "std")]
 use sp_version::RuntimeVersion;
+#[cfg(feature = "std")]
+use sp_runtime::{generic::Era, ApplyExtrinsicResult};
 
 #[macro_use]
 pub mod extrinsic;
+#[cfg(feature = "async")]
+pub mod async_api;
+#[cfg(feature = "std")]
+pub mod dry_run;
 #[cfg(feature = "std")]
 pub mod events;
 #[cfg(feature = "std")]
 pub mod node_metadata;
+#[cfg(feature = "std")]
+pub mod nonce;
+#[cfg(feature = "std")]
+pub mod offline_signing;
 
 #[cfg(feature = "std")]
 pub mod utils;
 #[cfg(feature = "std")]
 pub mod rpc;
+pub mod runtime;
+pub mod signer;
+#[cfg(feature = "test-support")]
+pub mod test_support;
 
 #[cfg(feature = "std")]
-use events::{EventsDecoder, RawEvent, RuntimeEvent};
+use extrinsic::xt_primitives::{ExtraParams, SignedExtra, SignedExtraOf, UncheckedExtrinsicV4};
+#[cfg(feature = "std")]
+use std::marker::PhantomData;
+use runtime::{DefaultRuntime, Runtime};
+#[cfg(feature = "std")]
+use signer::{sign_extrinsic, Signer};
+#[cfg(feature = "std")]
+use events::{EventsDecoder, EventsError, ExtrinsicReport, RawEvent, RuntimeEvent};
+#[cfg(feature = "std")]
+use dry_run::DryRunResult;
+#[cfg(feature = "std")]
+use nonce::{NonceManager, Sent};
+#[cfg(feature = "std")]
+use offline_signing::{OfflineSigningError, SigningRequest, SigningResponse};
+#[cfg(feature = "std")]
+use sp_core::crypto::Ss58Codec;
+#[cfg(feature = "std")]
+use std::sync::Arc;
 #[cfg(feature = "std")]
-use sp_runtime::{AccountId32 as AccountId, MultiSigner, MultiSignature, traits::{Verify, IdentifyAccount}};
+use system::Phase;
+#[cfg(feature = "std")]
+use sp_runtime::{AccountId32, MultiSigner, MultiSignature, traits::{Verify, IdentifyAccount}};
 
 pub use sp_core::H256 as Hash;
-/// The block number type used in this runtime.
-pub type BlockNumber = u64;
+/// The block number type of the substrate node, see `runtime::Runtime` for other chains.
+pub type BlockNumber = <DefaultRuntime as Runtime>::BlockNumber;
+
 /// Index of a transaction.
-//fixme: make generic
-pub type Index = u32;
+pub type Index = <DefaultRuntime as Runtime>::Index;
 
 #[cfg(feature = "std")]
-pub use rpc::XtStatus;
+pub use rpc::{RuntimeDispatchInfo, TransactionStatus, XtStatus};
+#[cfg(feature = "async")]
+pub use async_api::AsyncApi;
 
-//fixme: make generic
-pub type Balance = u128;
+pub type Balance = <DefaultRuntime as Runtime>::Balance;
 
-pub type AccountData = AccountDataGen<Balance>;
-pub type AccountInfo = AccountInfoGen<Index, AccountData>;
+pub type AccountData<R = DefaultRuntime> = AccountDataGen<<R as Runtime>::Balance>;
+pub type AccountInfo<R = DefaultRuntime> = AccountInfoGen<<R as Runtime>::Index, AccountData<R>>;
 
 type AccountPublic = <MultiSignature as Verify>::Signer;
 
 #[cfg(feature = "std")]
 #[derive(Clone)]
-pub struct Api<P>
+pub struct Api<P, Client = WsRpcClient, R = DefaultRuntime>
 where
-    P: Pair,
-    MultiSignature: From<P::Signature>,
+    P: Signer<R::AccountId>,
+    Client: RpcTransport,
+    R: Runtime,
 {
-    url: String,
+    client: Client,
     pub signer: Option<P>,
-    pub genesis_hash: Hash,
+    pub genesis_hash: R::Hash,
     pub metadata: Metadata,
     pub runtime_version: RuntimeVersion,
+    /// Time to wait for the response to a request.
+    pub request_timeout: Duration,
+    /// Time to wait for an extrinsic sent with `XtStatus::InBlock` or `XtStatus::Finalized` to be
+    /// included in a block or finalized.
+    pub finalization_timeout: Duration,
+    /// Number of blocks composed extrinsics are valid for, or `None` for immortal extrinsics.
+    pub era_period: Option<u64>,
+    /// Tip for the block author included in composed extrinsics.
+    pub tip: R::Balance,
+    /// Inputs of the runtime's own signed extensions, see `SignedExtra::Custom`.
+    pub custom_extra_params: <R::SignedExtra as SignedExtra>::Custom,
+    nonce_manager: Option<Arc<NonceManager>>,
+    runtime: PhantomData<R>,
+}
+
+/// Time to wait for an extrinsic to be finalized, unless configured otherwise.
+#[cfg(feature = "std")]
+pub const DEFAULT_FINALIZATION_TIMEOUT: Duration = Duration::from_secs(300);
+
+#[cfg(feature = "std")]
+pub type ApiResult<T> = Result<T, ApiError>;
+
+#[cfg(feature = "std")]
+#[derive(Debug, thiserror::Error)]
+pub enum ApiError {
+    #[error("Transport error: {0}")]
+    Transport(RpcError),
+    #[error("Node responded with {0}")]
+    JsonRpc(JsonRpcError),
+    #[error("Invalid hex string: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("Scale codec error: {0}")]
+    Codec(#[from] CodecError),
+    #[error("Invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Metadata error: {0}")]
+    Metadata(#[from] MetadataError),
+    #[error("Events error: {0}")]
+    Events(#[from] EventsError),
+    #[error("No response from node within {0:?}")]
+    Timeout(Duration),
+    #[error("No signer set")]
+    NoSigner,
+    #[error("Signer failed: {0}")]
+    Signer(String),
+    #[error("Can only wait for finalized, in block or ready extrinsic status, not {0:?}")]
+    UnsupportedXtStatus(XtStatus),
+    #[error("Extrinsic is not signed")]
+    NotSigned,
+    #[error("Extrinsic is signed by {0}, not by the signer")]
+    WrongSigner(String),
+    #[error("Tip {new} must be higher than the tip {old} of the replaced extrinsic")]
+    TipNotHigher { old: String, new: String },
+    /// The block number as `u64`, which every `Runtime::BlockNumber` converts into.
+    #[error("Block {0} not found")]
+    BlockNotFound(u64),
+    #[error("Block number {0} is out of range of the json rpc")]
+    BlockNumberOutOfRange(u64),
+    #[error("Extrinsic not found in block {0}")]
+    ExtrinsicNotInBlock(String),
+    #[error("Extrinsic will not be included: {0:?}")]
+    NotIncluded(TransactionStatus),
+    #[error("Offline signing failed: {0}")]
+    OfflineSigning(#[from] OfflineSigningError),
+}
+
+#[cfg(feature = "std")]
+fn signer_error<E: std::fmt::Debug>(error: E) -> ApiError {
+    ApiError::Signer(format!("{:?}", error))
+}
+
+#[cfg(feature = "std")]
+impl From<RpcError> for ApiError {
+    fn from(error: RpcError) -> Self {
+        match error {
+            RpcError::JsonRpc(error) => ApiError::JsonRpc(error),
+            RpcError::Timeout(timeout) => ApiError::Timeout(timeout),
+            error => ApiError::Transport(error),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P> Api<P, WsRpcClient>
+where
+    P: Signer,
+{
+    pub fn new(url: String) -> ApiResult<Self> {
+        let client = WsRpcClient::new(&url).map_err(RpcError::from)?;
+        Self::new_with_transport(client)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<P> Api<P, FailoverRpcClient>
+where
+    P: Signer,
+{
+    /// Connects to several nodes of the same chain and fails over between them. See
+    /// `rpc::FailoverRpcClient`.
+    pub fn new_with_endpoints(urls: &[&str]) -> ApiResult<Self> {
+        let client = FailoverRpcClient::new(urls)?;
+        Self::new_with_transport(client)
+    }
 }
 
 #[cfg(feature = "std")]
-impl<P> Api<P>
+impl<P, Client, R> Api<P, Client, R>
 where
-    P: Pair,
-    MultiSignature: From<P::Signature>,
+    P: Signer<R::AccountId>,
+    Client: RpcTransport,
+    R: Runtime,
 {
-    pub fn new(url: String) -> Self {
-        let genesis_hash = Self::_get_genesis_hash(url.clone());
+    /// Creates an api that sends its requests through `client`, e.g. a `rpc::MockRpcClient`.
+    /// Name the runtime here to talk to a chain with other types than the substrate node, e.g.
+    /// `Api::<P, WsRpcClient, MyChain>::new_with_transport(WsRpcClient::new(&url)?)`.
+    pub fn new_with_transport(client: Client) -> ApiResult<Self> {
+        let timeout = DEFAULT_REQUEST_TIMEOUT;
+        let genesis_hash = Self::_get_genesis_hash(&client, timeout)?;
         info!("Got genesis hash: {:?}", genesis_hash);
 
-        let meta = Self::_get_metadata(url.clone());
-        let metadata = Metadata::try_from(meta).unwrap();
+        let meta = Self::_get_metadata(&client, timeout)?;
+        let metadata = Metadata::try_from(meta)?;
         debug!("Metadata: {:?}", metadata);
 
-        let runtime_version = Self::_get_runtime_version(url.clone());
+        let runtime_version = Self::_get_runtime_version(&client, timeout)?;
         info!("Runtime Version: {:?}", runtime_version);
 
-        Self {
-            url,
+        Ok(Self {
+            client,
             signer: None,
             genesis_hash,
             metadata,
             runtime_version,
-        }
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            finalization_timeout: DEFAULT_FINALIZATION_TIMEOUT,
+            era_period: None,
+            tip: R::Balance::default(),
+            custom_extra_params: Default::default(),
+            nonce_manager: None,
+            runtime: PhantomData,
+        })
     }
 
+    /// Sets the signer of composed extrinsics. Disables the nonce manager of a previous signer.
     pub fn set_signer(mut self, signer: P) -> Self {
         self.signer = Some(signer);
+        self.nonce_manager = None;
+        self
+    }
+
+    /// Hands out the nonces of the signer locally instead of reading them from the chain state,
+    /// so that extrinsics still in the transaction pool are counted. Clones of the api share the
+    /// nonces, also across threads. See `nonce::NonceManager`.
+    pub fn enable_nonce_manager(mut self) -> ApiResult<Self>
+    where
+        R::AccountId: Ss58Codec,
+    {
+        let account = self.signer.as_ref().ok_or(ApiError::NoSigner)?.account_id();
+        self.nonce_manager = Some(Arc::new(NonceManager::new(account.to_ss58check())));
+        Ok(self)
+    }
+
+    pub fn set_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
         self
     }
 
-    fn _get_genesis_hash(url: String) -> Hash {
+    pub fn set_finalization_timeout(mut self, timeout: Duration) -> Self {
+        self.finalization_timeout = timeout;
+        self
+    }
+
+    /// Includes `tip` in composed extrinsics to prioritize them in the transaction pool.
+    pub fn set_tip(mut self, tip: R::Balance) -> Self {
+        self.tip = tip;
+        self
+    }
+
+    /// Passes `params` to the runtime's own signed extensions of composed extrinsics.
+    pub fn set_custom_extra_params(
+        mut self,
+        params: <R::SignedExtra as SignedExtra>::Custom,
+    ) -> Self {
+        self.custom_extra_params = params;
+        self
+    }
+
+    /// Composes mortal extrinsics that are valid for about `period` blocks, see `get_era`.
+    pub fn set_era_period(mut self, period: u64) -> Self {
+        self.era_period = Some(period);
+        self
+    }
+
+    fn _get_genesis_hash(client: &Client, timeout: Duration) -> ApiResult<R::Hash> {
         let jsonreq = json_req::chain_get_genesis_hash();
-        let genesis_hash_str = Self::_get_request(url, jsonreq.to_string())
-            .expect("Fetching genesis hash from node failed");
-        hexstr_to_hash(genesis_hash_str).unwrap()
+        let genesis_hash_str = Self::_get_request(client, jsonreq, timeout)?;
+        decode_hash(genesis_hash_str)
     }
 
-    fn _get_runtime_version(url: String) -> RuntimeVersion {
+    fn _get_runtime_version(client: &Client, timeout: Duration) -> ApiResult<RuntimeVersion> {
         let jsonreq = json_req::state_get_runtime_version();
-        let version_str = Self::_get_request(url, jsonreq.to_string()).unwrap(); //expect("Fetching runtime version from node failed");
+        let version_str = Self::_get_request(client, jsonreq, timeout)?;
         debug!("got the following runtime version (raw): {}", version_str);
-        serde_json::from_str(&version_str).unwrap()
+        Ok(serde_json::from_str(&version_str)?)
     }
 
-    fn _get_metadata(url: String) -> RuntimeMetadataPrefixed {
+    fn _get_metadata(client: &Client, timeout: Duration) -> ApiResult<RuntimeMetadataPrefixed> {
         let jsonreq = json_req::state_get_metadata();
-        let metadata_str = Self::_get_request(url, jsonreq.to_string()).unwrap();
+        let metadata_str = Self::_get_request(client, jsonreq, timeout)?;
 
-        let _unhex = hexstr_to_vec(metadata_str).unwrap();
+        let _unhex = hexstr_to_vec(metadata_str)?;
         let mut _om = _unhex.as_slice();
-        RuntimeMetadataPrefixed::decode(&mut _om).unwrap()
+        Ok(RuntimeMetadataPrefixed::decode(&mut _om)?)
     }
 
     // low level access
-    fn _get_request(url: String, jsonreq: String) -> WsResult<String> {
-        let (result_in, result_out) = channel();
-        rpc::get(url, jsonreq, result_in);
-
-        Ok(result_out.recv().unwrap())
+    fn _get_request(client: &Client, jsonreq: Value, timeout: Duration) -> ApiResult<String> {
+        Ok(client.get_request(jsonreq, timeout)?)
     }
 
-    pub fn get_metadata(&self) -> RuntimeMetadataPrefixed {
-        Self::_get_metadata(self.url.clone())
+    pub fn get_metadata(&self) -> ApiResult<RuntimeMetadataPrefixed> {
+        Self::_get_metadata(&self.client, self.request_timeout)
     }
 
-    pub fn get_spec_version(&self) -> u32 {
-        Self::_get_runtime_version(self.url.clone()).spec_version
+    pub fn get_spec_version(&self) -> ApiResult<u32> {
+        Self::_get_runtime_version(&self.client, self.request_timeout).map(|v| v.spec_version)
     }
 
-    pub fn get_genesis_hash(&self) -> Hash {
-        Self::_get_genesis_hash(self.url.clone())
+    pub fn get_genesis_hash(&self) -> ApiResult<R::Hash> {
+        Self::_get_genesis_hash(&self.client, self.request_timeout)
     }
 
-    pub fn get_nonce(&self) -> Result<u32, &str> {
+    pub fn get_nonce(&self) -> ApiResult<R::Index> {
         match &self.signer {
-            Some(pair) => {
-                let mut arr: [u8; 32] = Default::default();
-                arr.clone_from_slice(pair.to_owned().public().as_ref());
-                let accountid: AccountId = Decode::decode(&mut &arr.encode()[..]).unwrap();
-                if let Some(info) = self.get_account_info(&accountid) {
-                    Ok(info.nonce)
-                } else { Ok(0) }
+            Some(signer) => {
+                Ok(self
+                    .get_account_info(&signer.account_id())?
+                    .map_or(R::Index::default(), |info| info.nonce))
             }
-            None => Err("Can't get )nonce when no signer is set"),
+            None => Err(ApiError::NoSigner),
+        }
+    }
+
+    /// The nonce handed out and held by the nonce manager, if enabled, or `None`. The caller must
+    /// track or release it.
+    fn managed_nonce(&self) -> ApiResult<Option<u64>> {
+        match &self.nonce_manager {
+            Some(nonces) => Ok(Some(
+                nonces.next(|account| self.get_account_next_index(account))?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Gives the nonce of `xt`, composed but not going to be sent, back to the nonce manager.
+    pub fn release_nonce<Call: Encode>(&self, xt: &UncheckedExtrinsicV4<Call, R>) {
+        if let Some(nonces) = &self.nonce_manager {
+            nonces.sent(&xt.encode(), Sent::Rejected);
+        }
+    }
+
+    /// Tells the nonce manager, if enabled, what became of the extrinsic `xthex_prefixed`.
+    fn report_sent(&self, xthex_prefixed: &str, sent: Sent) {
+        let nonces = match &self.nonce_manager {
+            Some(nonces) => nonces,
+            None => return,
+        };
+        if sent != Sent::Accepted {
+            info!("nonces of {} may be off, resyncing: {:?}", nonces.account(), sent);
         }
+        if let Ok(xt) = hexstr_to_vec(xthex_prefixed.to_string()) {
+            nonces.sent(&xt, sent);
+        }
+    }
+
+    /// The next nonce of the SS58 `account`, including its extrinsics in the transaction pool.
+    pub fn get_account_next_index(&self, account: &str) -> ApiResult<u64> {
+        let jsonreq = json_req::system_account_next_index(account);
+        let index = Self::_get_request(&self.client, jsonreq, self.request_timeout)?;
+        Ok(serde_json::from_str(&index)?)
     }
 
-    pub fn get_account_info(&self, address: &AccountId) -> Option<AccountInfo> {
-        let id: &[u8; 32] = address.as_ref();
+    pub fn get_account_info(&self, address: &R::AccountId) -> ApiResult<Option<AccountInfo<R>>> {
         let storagekey: sp_core::storage::StorageKey = self.metadata
-            .module("System").unwrap()
-            .storage("Account").unwrap()
-            .get_map::<AccountId,AccountInfo>().unwrap()
+            .module("System")?
+            .storage("Account")?
+            .get_map::<R::AccountId, AccountInfo<R>>()?
             .key(address.clone());
         info!("storagekey {:?}",storagekey);
         info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
         self.get_storage_by_key_hash(storagekey.0)
     }
 
-    pub fn get_account_data(&self, address: &AccountId) -> Option<AccountData> {
-        if let Some(info) = self.get_account_info(address) {
-            Some(info.data)
-        } else { None }
+    pub fn get_account_data(&self, address: &R::AccountId) -> ApiResult<Option<AccountData<R>>> {
+        Ok(self.get_account_info(address)?.map(|info| info.data))
+    }
+
+    pub fn get_finalized_head(&self) -> ApiResult<String> {
+        Self::_get_request(&self.client, json_req::chain_get_finalized_head(), self.request_timeout)
+    }
+
+    pub fn get_header(&self, hash: Option<R::Hash>) -> ApiResult<String> {
+        Self::_get_request(&self.client, json_req::chain_get_header(hash), self.request_timeout)
+    }
+
+    pub fn get_block(&self, hash: Option<R::Hash>) -> ApiResult<String> {
+        Self::_get_request(&self.client, json_req::chain_get_block(hash), self.request_timeout)
+    }
+
+    /// Number of the block `hash`, or of the best block if `None`.
+    pub fn get_block_number(&self, hash: Option<R::Hash>) -> ApiResult<R::BlockNumber> {
+        Ok(Header::<R>::from_json(&self.get_header(hash)?)?.number)
     }
 
-    pub fn get_finalized_head(&self) -> WsResult<String> {
-        Self::_get_request(self.url.clone(), json_req::chain_get_finalized_head().to_string())
+    /// Hash of the block `number`, or of the best block if `None`.
+    pub fn get_block_hash(&self, number: Option<R::BlockNumber>) -> ApiResult<Option<R::Hash>> {
+        let number = match number.map(Into::<u64>::into) {
+            Some(n) => Some(u32::try_from(n).map_err(|_| ApiError::BlockNumberOutOfRange(n))?),
+            None => None,
+        };
+        let jsonreq = json_req::chain_get_block_hash(number);
+        let hash_str = Self::_get_request(&self.client, jsonreq, self.request_timeout)?;
+        match hash_str.as_str() {
+            "null" => Ok(None),
+            _ => Ok(Some(decode_hash(hash_str)?)),
+        }
     }
 
-    pub fn get_header(&self, hash: Option<Hash>) -> WsResult<String> {
-        Self::_get_request(self.url.clone(), json_req::chain_get_header(hash).to_string())
+    /// The era of extrinsics composed now and the hash of its checkpoint block, which is signed
+    /// along. Mortal starting at the best block if `era_period` is set, immortal otherwise.
+    ///
+    /// Immortal eras need no request. Mortal ones need the best header, whose hash is the
+    /// checkpoint unless the period is longer than 4096 blocks and the era starts earlier.
+    pub fn get_era(&self) -> ApiResult<(Era, R::Hash)> {
+        let period = match self.era_period {
+            Some(period) => period,
+            None => return Ok((Era::Immortal, self.genesis_hash)),
+        };
+        let best = Header::<R>::from_json(&self.get_header(None)?)?;
+        let era = Era::mortal(period, best.number.into());
+        Ok((era, self.era_checkpoint(era, &best)?))
+    }
+
+    /// The hash of the block the mortal `era` started at, as of the `best` block.
+    fn era_checkpoint(&self, era: Era, best: &Header<R>) -> ApiResult<R::Hash> {
+        let best_number: u64 = best.number.into();
+        let birth = era.birth(best_number);
+        if birth == best_number {
+            return Ok(best.hash);
+        }
+        self.get_block_hash(Some(block_number::<R>(birth)?))?
+            .ok_or(ApiError::BlockNotFound(birth))
     }
 
-    pub fn get_block(&self, hash: Option<Hash>) -> WsResult<String> {
-        Self::_get_request(self.url.clone(), json_req::chain_get_block(hash).to_string())
+    /// Sends a raw json request. Its `id` is replaced with a unique one of this connection.
+    pub fn get_request(&self, jsonreq: Value) -> ApiResult<String> {
+        Self::_get_request(&self.client, jsonreq, self.request_timeout)
     }
 
-    pub fn get_request(&self, jsonreq: String) -> WsResult<String> {
-        Self::_get_request(self.url.clone(), jsonreq)
+    /// Sends all requests in one round-trip. Fails if the batch could not be sent, otherwise
+    /// returns the result of every request in the order of `jsonreqs`.
+    pub fn get_request_batch(&self, jsonreqs: Vec<Value>) -> ApiResult<Vec<ApiResult<String>>> {
+        let results = self.client.batch_request(jsonreqs, self.request_timeout)?;
+        Ok(results.into_iter().map(|res| res.map_err(ApiError::from)).collect())
     }
 
     pub fn get_storage_value<V:Decode+Clone>(
         &self,
         storage_prefix: &'static str,
         storage_key_name: &'static str,
-    ) -> Option<V> {
+    ) -> ApiResult<Option<V>> {
         let storagekey: sp_core::storage::StorageKey = self.metadata
-                .module(storage_prefix).unwrap()
-                .storage(storage_key_name).unwrap()
-                .get_value().unwrap()
+                .module(storage_prefix)?
+                .storage(storage_key_name)?
+                .get_value()?
                 .key();
         info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
         self.get_storage_by_key_hash(storagekey.0)
@@ -248,32 +530,32 @@ where
         storage_prefix: &'static str,
         storage_key_name: &'static str,
         map_key: K,
-    ) -> Option<V> {
+    ) -> ApiResult<Option<V>> {
         let storagekey: sp_core::storage::StorageKey = self.metadata
-                .module(storage_prefix).unwrap()
-                .storage(storage_key_name).unwrap()
-                .get_map::<K, V>().unwrap()
+                .module(storage_prefix)?
+                .storage(storage_key_name)?
+                .get_map::<K, V>()?
                 .key(map_key);
         info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
         self.get_storage_by_key_hash(storagekey.0)
     }
 
-    pub fn get_storage_by_key_hash<V:Decode+Clone>(&self, hash: Vec<u8>) -> Option<V> {
+    pub fn get_storage_by_key_hash<V:Decode+Clone>(&self, hash: Vec<u8>) -> ApiResult<Option<V>> {
         let mut keyhash_str = hex::encode(hash);
         keyhash_str.insert_str(0, "0x");
         let jsonreq = json_req::state_get_storage(&keyhash_str);
-        if let Ok(hexstr) = Self::_get_request(self.url.clone(), jsonreq.to_string()) {
-            info!("storage hex = {}", hexstr);
-            let hexstr = hexstr
-            .trim_matches('\"')
-            .to_string()
-            .trim_start_matches("0x")
-            .to_string();
-            match hexstr.as_str() {
-                "null" => None,
-                _ => Some(Decode::decode(&mut &hex::decode(&hexstr).unwrap()[..]).unwrap()),
-            }
-        } else { None }
+        let hexstr = Self::_get_request(&self.client, jsonreq, self.request_timeout)?;
+        decode_storage(hexstr)
+    }
+
+    /// Reads the storage at all `hashes` in one round-trip, see `get_request_batch`.
+    pub fn get_storage_by_key_hashes<V:Decode+Clone>(&self, hashes: Vec<Vec<u8>>) -> ApiResult<Vec<ApiResult<Option<V>>>> {
+        let jsonreqs = hashes
+            .into_iter()
+            .map(|hash| json_req::state_get_storage(&format!("0x{}", hex::encode(hash))))
+            .collect();
+        let results = self.get_request_batch(jsonreqs)?;
+        Ok(results.into_iter().map(|res| res.and_then(decode_storage)).collect())
     }
 
     pub fn get_storage_double_map<K: Encode,Q: Encode, V:Decode+Clone> (
@@ -282,63 +564,423 @@ where
         storage_key_name: &'static str,
         first: K,
         second: Q,
-    ) -> Option<V> {
+    ) -> ApiResult<Option<V>> {
         let storagekey: sp_core::storage::StorageKey = self.metadata
-                .module(storage_prefix).unwrap()
-                .storage(storage_key_name).unwrap()
-                .get_double_map::<K, Q, V>().unwrap()
+                .module(storage_prefix)?
+                .storage(storage_key_name)?
+                .get_double_map::<K, Q, V>()?
                 .key(first, second);
         info!("storage key is: 0x{}", hex::encode(storagekey.0.clone()));
         self.get_storage_by_key_hash(storagekey.0)
     }
 
-    pub fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> WsResult<Option<Hash>> {
+    /// Submits an extrinsic without watching its status and returns the extrinsic's hash.
+    /// Unlike `send_extrinsic`, this does not need a subscription, so it works with every transport.
+    pub fn submit_extrinsic(&self, xthex_prefixed: String) -> ApiResult<Hash> {
+        debug!("submitting extrinsic: {:?}", xthex_prefixed);
+        let jsonreq = json_req::author_submit_extrinsic(&xthex_prefixed);
+        let res = Self::_get_request(&self.client, jsonreq, self.request_timeout);
+        self.report_sent(&xthex_prefixed, sent(&res));
+        let res = res?;
+        info!("submitted: {}", res);
+        Ok(hexstr_to_hash(res)?)
+    }
+
+    pub fn send_extrinsic(&self, xthex_prefixed: String, exit_on: XtStatus) -> ApiResult<Option<Hash>> {
         debug!("sending extrinsic: {:?}", xthex_prefixed);
 
-        let jsonreq = json_req::author_submit_and_watch_extrinsic(&xthex_prefixed).to_string();
+        let jsonreq = json_req::author_submit_and_watch_extrinsic(&xthex_prefixed);
 
+        let on_message_fn: rpc::OnMessageFn = match exit_on {
+            XtStatus::Finalized => rpc::on_extrinsic_msg_until_finalized,
+            XtStatus::InBlock => rpc::on_extrinsic_msg_until_in_block,
+            XtStatus::Ready => rpc::on_extrinsic_msg_until_ready,
+            _ => return Err(ApiError::UnsupportedXtStatus(exit_on)),
+        };
+        let timeout = match exit_on {
+            XtStatus::Finalized | XtStatus::InBlock => self.finalization_timeout,
+            _ => self.request_timeout,
+        };
         let (result_in, result_out) = channel();
+        let status = self
+            .client
+            .subscribe(jsonreq, result_in.into(), on_message_fn)
+            .map_err(ApiError::from)
+            .and_then(|id| match result_out.recv_timeout(timeout) {
+                Ok(msg) => Ok(rpc::parse_transaction_status(&msg)?),
+                Err(RecvTimeoutError::Timeout) => {
+                    // the extrinsic may still be included, we just stop watching it
+                    self.client.cancel(&id);
+                    Err(ApiError::Timeout(timeout))
+                }
+                Err(RecvTimeoutError::Disconnected) => Err(RpcError::ConnectionLost.into()),
+            });
+        let sent = match &status {
+            Ok(TransactionStatus::Future) => Sent::Future,
+            Ok(TransactionStatus::Dropped) | Ok(TransactionStatus::Invalid) => Sent::Rejected,
+            other => sent(other),
+        };
+        self.report_sent(&xthex_prefixed, sent);
+        status_result(status?)
+    }
+
+    /// Sends an extrinsic like `send_extrinsic` and, once it is in a block, returns whether its
+    /// dispatch succeeded and the events it emitted. `exit_on` must be `InBlock` or `Finalized`.
+    pub fn send_extrinsic_with_report(
+        &self,
+        xthex_prefixed: String,
+        exit_on: XtStatus,
+    ) -> ApiResult<ExtrinsicReport<R::Hash>> {
         match exit_on {
-            XtStatus::Finalized => {
-                rpc::send_extrinsic_and_wait_until_finalized(
-                    self.url.clone(),
-                    jsonreq.clone(),
-                    result_in.clone(),
-                );
-                let res = result_out.recv().unwrap();
-                info!("finalized: {}", res);
-                Ok(Some(hexstr_to_hash(res).unwrap()))
+            XtStatus::InBlock | XtStatus::Finalized => {}
+            _ => return Err(ApiError::UnsupportedXtStatus(exit_on)),
+        }
+        // watching only ends before inclusion for an extrinsic with a future nonce
+        let block_hash = self
+            .send_extrinsic(xthex_prefixed.clone(), exit_on)?
+            .ok_or(ApiError::NotIncluded(TransactionStatus::Future))?;
+        let block_hash = R::Hash::decode(&mut block_hash.as_bytes())?;
+        self.get_extrinsic_report(&xthex_prefixed, block_hash)
+    }
+
+    /// Re-signs the pending `xt` with the same nonce and era but a higher `tip` and sends it, so
+    /// that it replaces `xt` in the transaction pool. `xt` must be signed by `signer`.
+    pub fn resubmit_with_tip<Call: Encode + Clone>(
+        &self,
+        xt: &UncheckedExtrinsicV4<Call, R>,
+        tip: R::Balance,
+        exit_on: XtStatus,
+    ) -> ApiResult<Option<Hash>>
+    where
+        R::Balance: PartialOrd,
+        R::Address: PartialEq,
+    {
+        let replacement = self.compose_replacement(xt, tip)?;
+        self.send_extrinsic(replacement.hex_encode(), exit_on)
+    }
+
+    /// Composes the replacement of the pending `xt` with a higher `tip`, see `resubmit_with_tip`.
+    /// Fails with `ApiError::WrongSigner` if `xt` is not signed by `signer`.
+    pub fn compose_replacement<Call: Encode + Clone>(
+        &self,
+        xt: &UncheckedExtrinsicV4<Call, R>,
+        tip: R::Balance,
+    ) -> ApiResult<UncheckedExtrinsicV4<Call, R>>
+    where
+        R::Balance: PartialOrd,
+        R::Address: PartialEq,
+    {
+        let signer = self.signer.as_ref().ok_or(ApiError::NoSigner)?;
+        let (address, _, extra) = xt.signature.as_ref().ok_or(ApiError::NotSigned)?;
+        if *address != R::Address::from(signer.account_id()) {
+            return Err(ApiError::WrongSigner(format!("{:?}", address)));
+        }
+        let old_tip = <R::SignedExtra as SignedExtra>::tip(extra);
+        if tip <= old_tip {
+            return Err(ApiError::TipNotHigher {
+                old: format!("{:?}", old_tip),
+                new: format!("{:?}", tip),
+            });
+        }
+        let era = <R::SignedExtra as SignedExtra>::era(extra);
+        let era_checkpoint = match era {
+            Era::Immortal => self.genesis_hash,
+            Era::Mortal(..) => {
+                let best = Header::<R>::from_json(&self.get_header(None)?)?;
+                self.era_checkpoint(era, &best)?
+            }
+        };
+        let params = ExtraParams {
+            nonce: <R::SignedExtra as SignedExtra>::nonce(extra),
+            tip,
+            era,
+            era_checkpoint,
+            genesis_hash: self.genesis_hash,
+            spec_version: self.runtime_version.spec_version,
+            custom: self.custom_extra_params.clone(),
+        };
+        sign_extrinsic(signer, xt.function.clone(), &params).map_err(signer_error)
+    }
+
+    /// Signs `call` with `signer`, if set, and the current nonce, era and tip. Used by
+    /// `compose_extrinsic!`.
+    pub fn compose_extrinsic_from_call<Call: Encode + Clone>(
+        &self,
+        call: Call,
+    ) -> ApiResult<UncheckedExtrinsicV4<Call, R>> {
+        let signer = match &self.signer {
+            Some(signer) => signer,
+            None => {
+                return Ok(UncheckedExtrinsicV4 {
+                    signature: None,
+                    function: call,
+                })
+            }
+        };
+        let (era, era_checkpoint) = self.get_era()?;
+        let managed_nonce = self.managed_nonce()?;
+        let params = ExtraParams {
+            nonce: match managed_nonce {
+                Some(nonce) => index::<R>(nonce)?,
+                None => self.get_nonce()?,
             },
-            XtStatus::Ready => {
-                rpc::send_extrinsic(
-                    self.url.clone(),
-                    jsonreq.clone(),
-                    result_in.clone(),
-                );
-                let res = result_out.recv().unwrap();
-                info!("ready: {}", res);
-                Ok(None)
+            tip: self.tip,
+            era,
+            era_checkpoint,
+            genesis_hash: self.genesis_hash,
+            spec_version: self.runtime_version.spec_version,
+            custom: self.custom_extra_params.clone(),
+        };
+        let xt = sign_extrinsic(signer, call, &params).map_err(signer_error);
+        if let (Some(nonces), Some(nonce)) = (&self.nonce_manager, managed_nonce) {
+            match &xt {
+                Ok(xt) => nonces.track(&xt.encode(), nonce),
+                Err(_) => nonces.release(nonce),
+            }
+        }
+        xt
+    }
+
+    /// Exports signing `call` by `account` with its current nonce, era and tip to sign it on an
+    /// offline machine, see `offline_signing`. The description names the call from the metadata.
+    /// If `account` is the signer and the nonce manager is enabled, the nonce is handed out by
+    /// it, import the response with `import_signing_response` or pass an abandoned request to
+    /// `release_signing_request`.
+    pub fn compose_signing_request<Call: Encode + std::fmt::Debug>(
+        &self,
+        account: &R::AccountId,
+        call: Call,
+    ) -> ApiResult<SigningRequest> {
+        let (era, era_checkpoint) = self.get_era()?;
+        let is_signer = match &self.signer {
+            Some(signer) => signer.account_id().encode() == account.encode(),
+            None => false,
+        };
+        let managed_nonce = if is_signer { self.managed_nonce()? } else { None };
+        let params = ExtraParams {
+            nonce: match managed_nonce {
+                Some(nonce) => index::<R>(nonce)?,
+                None => self
+                    .get_account_info(account)?
+                    .map_or(R::Index::default(), |info| info.nonce),
             },
-            _ => panic!("can only wait for finalized or ready extrinsic status"),
+            tip: self.tip,
+            era,
+            era_checkpoint,
+            genesis_hash: self.genesis_hash,
+            spec_version: self.runtime_version.spec_version,
+            custom: self.custom_extra_params.clone(),
+        };
+        let encoded = call.encode();
+        let description = match encoded.get(..2) {
+            Some(&[module, call_index]) => self.metadata.call_name(module, call_index),
+            _ => None,
+        }
+        .map_or_else(
+            || format!("{:?}", call),
+            |(module, name)| format!("{}::{} {:?}", module, name, call),
+        );
+        let request = SigningRequest::new::<_, R>(description, account, &call, &params);
+        if let (Some(nonces), Some(nonce)) = (&self.nonce_manager, managed_nonce) {
+            nonces.track(request.request_hash.to_lowercase().as_bytes(), nonce);
+        }
+        Ok(request)
+    }
+
+    /// Builds the extrinsic from the `response` to the exported `request`, see
+    /// `SigningRequest::into_extrinsic`, and hands its nonce over to the extrinsic, if managed.
+    pub fn import_signing_response<Call>(
+        &self,
+        request: &SigningRequest,
+        request_hash: &str,
+        response: &SigningResponse,
+    ) -> ApiResult<UncheckedExtrinsicV4<Call, R>>
+    where
+        Call: Encode + Decode,
+        R: Runtime<AccountId = AccountId32>,
+        SignedExtraOf<R>: Decode,
+    {
+        let xt = request.into_extrinsic(request_hash, response)?;
+        if let Some(nonces) = &self.nonce_manager {
+            nonces.retrack(request_hash.to_lowercase().as_bytes(), &xt.encode());
+        }
+        Ok(xt)
+    }
+
+    /// Gives the nonce of the exported `request`, that is not going to be signed or sent, back to
+    /// the nonce manager.
+    pub fn release_signing_request(&self, request: &SigningRequest) {
+        if let Some(nonces) = &self.nonce_manager {
+            nonces.sent(request.request_hash.to_lowercase().as_bytes(), Sent::Rejected);
         }
     }
 
-    pub fn subscribe_events(&self, sender: ThreadOut<String>) {
+    /// Weight, class and fee of `xt` in the state of block `at`, or the best block if `None`.
+    /// The fee is that of a signed `xt`, without its tip.
+    pub fn get_payment_info<Call: Encode>(
+        &self,
+        xt: &UncheckedExtrinsicV4<Call, R>,
+        at: Option<R::Hash>,
+    ) -> ApiResult<RuntimeDispatchInfo<R::Balance>>
+    where
+        R::Balance: DeserializeOwned,
+    {
+        let jsonreq = json_req::payment_query_info(&xt.hex_encode(), at);
+        let info = Self::_get_request(&self.client, jsonreq, self.request_timeout)?;
+        Ok(serde_json::from_str(&info)?)
+    }
+
+    /// Calls the runtime api `method`, e.g. `Core_version`, with the SCALE encoded arguments in
+    /// the state of block `at`, or the best block if `None`, and decodes its result. Fails if
+    /// the result is not entirely decoded, e.g. because `T` is not what `method` returns.
+    pub fn state_call<T: Decode>(
+        &self,
+        method: &str,
+        encoded_args: &[u8],
+        at: Option<R::Hash>,
+    ) -> ApiResult<T> {
+        let data = format!("0x{}", hex::encode(encoded_args));
+        let jsonreq = json_req::state_call(method, &data, at);
+        let result = Self::_get_request(&self.client, jsonreq, self.request_timeout)?;
+        Ok(T::decode_all(&hexstr_to_vec(result)?)?)
+    }
+
+    /// The nonce of `account` from the runtime api `AccountNonceApi`.
+    pub fn call_account_nonce(
+        &self,
+        account: &R::AccountId,
+        at: Option<R::Hash>,
+    ) -> ApiResult<R::Index> {
+        self.state_call("AccountNonceApi_account_nonce", &account.encode(), at)
+    }
+
+    /// The metadata from the runtime api `Metadata`.
+    pub fn call_metadata(&self, at: Option<R::Hash>) -> ApiResult<RuntimeMetadataPrefixed> {
+        let opaque: Vec<u8> = self.state_call("Metadata_metadata", &[], at)?;
+        Ok(RuntimeMetadataPrefixed::decode_all(&opaque)?)
+    }
+
+    /// The runtime version from the runtime api `Core`.
+    pub fn call_core_version(&self, at: Option<R::Hash>) -> ApiResult<RuntimeVersion> {
+        self.state_call("Core_version", &[], at)
+    }
+
+    /// Applies `xt` to the state of block `at`, or the best block if `None`, without including
+    /// it, to check whether it is valid and its dispatch succeeds. No fees are paid. The node
+    /// must expose the unsafe rpc method `system_dryRun`.
+    pub fn dry_run<Call: Encode>(
+        &self,
+        xt: &UncheckedExtrinsicV4<Call, R>,
+        at: Option<R::Hash>,
+    ) -> ApiResult<DryRunResult> {
+        let jsonreq = json_req::system_dry_run(&xt.hex_encode(), at);
+        let result = Self::_get_request(&self.client, jsonreq, self.request_timeout)?;
+        let result = ApplyExtrinsicResult::decode_all(&hexstr_to_vec(result)?)?;
+        Ok(DryRunResult::new(result, &self.metadata))
+    }
+
+    /// Looks up the extrinsic in the body of block `block_hash` and collects its events.
+    pub fn get_extrinsic_report(
+        &self,
+        xthex_prefixed: &str,
+        block_hash: R::Hash,
+    ) -> ApiResult<ExtrinsicReport<R::Hash>> {
+        let block: Value = serde_json::from_str(&self.get_block(Some(block_hash))?)?;
+        let extrinsic_index = block["block"]["extrinsics"]
+            .as_array()
+            .and_then(|xts| {
+                xts.iter().position(|xt| {
+                    xt.as_str()
+                        .map_or(false, |xt| xt.eq_ignore_ascii_case(xthex_prefixed))
+                })
+            })
+            .ok_or_else(|| ApiError::ExtrinsicNotInBlock(format!("{:?}", block_hash)))?;
+        let events = self.events_at(block_hash)?;
+        Ok(ExtrinsicReport::new(
+            block_hash,
+            extrinsic_index as u32,
+            events,
+            &self.metadata,
+        )?)
+    }
+
+    /// All events emitted in block `block_hash`.
+    pub fn get_events_at(&self, block_hash: R::Hash) -> ApiResult<Vec<(Phase, RuntimeEvent)>> {
+        self.events_at(block_hash)
+    }
+
+    /// `get_events_at` for the hashes of `TransactionStatus`, which are always `Hash`.
+    fn events_at<H: Encode>(&self, block_hash: H) -> ApiResult<Vec<(Phase, RuntimeEvent)>> {
+        let key = storage_key_hash("System", "Events", None);
+        let jsonreq = json_req::state_get_storage_at(&key, Some(block_hash));
+        let events_str = Self::_get_request(&self.client, jsonreq, self.request_timeout)?;
+        if events_str == "null" {
+            return Ok(Vec::new());
+        }
+        let events = hexstr_to_vec(events_str)?;
+        let event_decoder = EventsDecoder::for_runtime::<R>(self.metadata.clone())?;
+        Ok(event_decoder.decode_events(&mut events.as_slice())?)
+    }
+
+    /// Submits an extrinsic and returns all of its status updates, e.g. `Ready`, `Broadcast`,
+    /// `InBlock` and `Finalized`. Each update is awaited for at most `finalization_timeout`.
+    pub fn watch_extrinsic(&self, xthex_prefixed: String) -> ApiResult<TransactionStatusIter<'_>> {
+        debug!("watching extrinsic: {:?}", xthex_prefixed);
+        let jsonreq = json_req::author_submit_and_watch_extrinsic(&xthex_prefixed);
+        let (result_in, result_out) = channel();
+        let id = self
+            .client
+            .subscribe(jsonreq, result_in.into(), rpc::on_transaction_status_msg)?;
+        Ok(TransactionStatusIter {
+            client: &self.client,
+            id,
+            receiver: Some(result_out),
+            timeout: self.finalization_timeout,
+        })
+    }
+
+    pub fn subscribe_events(&self, sender: ThreadOut<String>) -> ApiResult<()> {
         debug!("subscribing to events");
         let key = storage_key_hash("System", "Events", None);
-        let jsonreq = json_req::state_subscribe_storage(&key).to_string();
+        let jsonreq = json_req::state_subscribe_storage(&key);
+
+        self.client.subscribe(jsonreq, sender.into(), rpc::on_subscription_msg)?;
+        Ok(())
+    }
+
+    /// Sends the header of every new block as json string to `sender`.
+    pub fn subscribe_new_heads(&self, sender: ThreadOut<String>) -> ApiResult<()> {
+        debug!("subscribing to new heads");
+        let jsonreq = json_req::chain_subscribe_new_heads();
+        self.client.subscribe(jsonreq, sender.into(), rpc::on_notification_msg)?;
+        Ok(())
+    }
 
-        rpc::start_event_subscriber(self.url.clone(), jsonreq, sender);
+    /// Sends the header of every newly finalized block as json string to `sender`.
+    pub fn subscribe_finalized_heads(&self, sender: ThreadOut<String>) -> ApiResult<()> {
+        debug!("subscribing to finalized heads");
+        let jsonreq = json_req::chain_subscribe_finalized_heads();
+        self.client.subscribe(jsonreq, sender.into(), rpc::on_notification_msg)?;
+        Ok(())
     }
 
+    /// Sends the url of the node to `sender` whenever the connection has been re-established after
+    /// it was lost. Event and head subscriptions are resumed, but the blocks in between have to be
+    /// fetched with `get_block` and friends.
+    pub fn subscribe_reconnects(&self, sender: ThreadOut<String>) {
+        self.client.subscribe_reconnects(sender.into())
+    }
+
+    /// Waits for the next event `module::variant` received on `receiver`, for at most
+    /// `finalization_timeout`.
     pub fn wait_for_event<E: Decode>(
         &self,
         module: &str,
         variant: &str,
         receiver: &Receiver<String>,
-    ) -> Option<Result<E, CodecError>> {
-        self.wait_for_raw_event(module, variant, receiver)
-            .map(|raw| E::decode(&mut &raw.data[..]))
+    ) -> ApiResult<E> {
+        let raw = self.wait_for_raw_event(module, variant, receiver)?;
+        Ok(E::decode(&mut &raw.data[..])?)
     }
 
     pub fn wait_for_raw_event(
@@ -346,14 +988,22 @@ where
         module: &str,
         variant: &str,
         receiver: &Receiver<String>,
-    ) -> Option<RawEvent> {
+    ) -> ApiResult<RawEvent> {
+        let event_decoder = EventsDecoder::for_runtime::<R>(self.metadata.clone())?;
+        let deadline = Instant::now() + self.finalization_timeout;
         loop {
-            let event_str = receiver.recv().unwrap();
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let event_str = match receiver.recv_timeout(remaining) {
+                Ok(event_str) => event_str,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(ApiError::Timeout(self.finalization_timeout))
+                }
+                Err(RecvTimeoutError::Disconnected) => return Err(RpcError::ConnectionLost.into()),
+            };
 
-            let _unhex = hexstr_to_vec(event_str).unwrap();
+            let _unhex = hexstr_to_vec(event_str)?;
             let mut _er_enc = _unhex.as_slice();
 
-            let event_decoder = EventsDecoder::try_from(self.metadata.clone()).unwrap();
             let _events = event_decoder.decode_events(&mut _er_enc);
             info!("wait for raw event");
             match _events {
@@ -364,14 +1014,566 @@ where
                             RuntimeEvent::Raw(raw)
                                 if raw.module == module && raw.variant == variant =>
                             {
-                                return Some(raw)
+                                return Ok(raw)
                             }
                             _ => debug!("ignoring unsupported module event: {:?}", event),
                         }
                     }
                 }
-                Err(_) => error!("couldn't decode event record list"),
+                Err(e) => error!("couldn't decode event record list: {:?}", e),
+            }
+        }
+    }
+}
+
+/// Status updates of an extrinsic submitted with `Api::watch_extrinsic`. Ends after the final
+/// status, or with the error the node rejected the extrinsic with. If there is no update within
+/// the timeout, the extrinsic is no longer watched and the iterator ends with `ApiError::Timeout`.
+#[cfg(feature = "std")]
+pub struct TransactionStatusIter<'a> {
+    client: &'a dyn RpcTransport,
+    id: String,
+    receiver: Option<Receiver<String>>,
+    timeout: Duration,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Iterator for TransactionStatusIter<'a> {
+    type Item = ApiResult<TransactionStatus>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let msg = match self.receiver.as_ref()?.recv_timeout(self.timeout) {
+            Ok(msg) => msg,
+            Err(RecvTimeoutError::Timeout) => {
+                // the extrinsic may still be included, we just stop watching it
+                self.client.cancel(&self.id);
+                self.receiver = None;
+                return Some(Err(ApiError::Timeout(self.timeout)));
+            }
+            Err(RecvTimeoutError::Disconnected) => return None,
+        };
+        Some(rpc::parse_transaction_status(&msg).map_err(ApiError::from))
+    }
+}
+
+/// Interprets the last message received for a watched extrinsic. Returns the hash of the block
+/// it was included in, if it has been.
+#[cfg(feature = "std")]
+pub(crate) fn extrinsic_result(msg: &str) -> ApiResult<Option<Hash>> {
+    status_result(rpc::parse_transaction_status(msg)?)
+}
+
+/// See `extrinsic_result`.
+#[cfg(feature = "std")]
+fn status_result(status: TransactionStatus) -> ApiResult<Option<Hash>> {
+    match status {
+        TransactionStatus::InBlock(hash) | TransactionStatus::Finalized(hash) => {
+            info!("included in block: {:?}", hash);
+            Ok(Some(hash))
+        }
+        status if status.is_final() => Err(ApiError::NotIncluded(status)),
+        status => {
+            info!("{:?}", status);
+            Ok(None)
+        }
+    }
+}
+
+/// Decodes a hash of the runtime's type from the hex string of a response.
+#[cfg(feature = "std")]
+pub(crate) fn decode_hash<H: Decode>(hexstr: String) -> ApiResult<H> {
+    Ok(H::decode(&mut hexstr_to_vec(hexstr)?.as_slice())?)
+}
+
+/// What the result of sending an extrinsic tells about its nonce.
+#[cfg(feature = "std")]
+fn sent<T>(result: &ApiResult<T>) -> Sent {
+    match result {
+        Ok(_) => Sent::Accepted,
+        Err(ApiError::JsonRpc(_)) => Sent::Rejected,
+        Err(_) => Sent::Unknown,
+    }
+}
+
+/// Converts a nonce from json to the type of runtime `R`.
+#[cfg(feature = "std")]
+fn index<R: Runtime>(nonce: u64) -> ApiResult<R::Index> {
+    R::Index::try_from(nonce)
+        .map_err(|_| CodecError::from("nonce out of range of the runtime type").into())
+}
+
+/// Converts a block number from json to the type of runtime `R`.
+#[cfg(feature = "std")]
+fn block_number<R: Runtime>(number: u64) -> ApiResult<R::BlockNumber> {
+    R::BlockNumber::try_from(number)
+        .map_err(|_| CodecError::from("block number out of range of the runtime type").into())
+}
+
+/// The number and hash of a block header, with the types of runtime `R`.
+#[cfg(feature = "std")]
+struct Header<R: Runtime> {
+    number: R::BlockNumber,
+    hash: R::Hash,
+}
+
+/// A header as the node sends it. The digest items are hex encoded.
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonHeader {
+    parent_hash: String,
+    number: String,
+    state_root: String,
+    extrinsics_root: String,
+    digest: JsonDigest,
+}
+
+#[cfg(feature = "std")]
+#[derive(Deserialize)]
+struct JsonDigest {
+    logs: Vec<String>,
+}
+
+#[cfg(feature = "std")]
+impl<R: Runtime> Header<R> {
+    /// Reads the header from the json of `chain_getHeader`. It is hashed with blake2_256 like
+    /// the substrate node's.
+    fn from_json(header: &str) -> ApiResult<Self> {
+        let header: JsonHeader = serde_json::from_str(header)?;
+        let number = u64::from_str_radix(header.number.trim_start_matches("0x"), 16)
+            .map_err(|_| CodecError::from("invalid block number"))?;
+        let mut encoded = hexstr_to_vec(header.parent_hash)?;
+        Compact(number).encode_to(&mut encoded);
+        encoded.extend(hexstr_to_vec(header.state_root)?);
+        encoded.extend(hexstr_to_vec(header.extrinsics_root)?);
+        Compact(header.digest.logs.len() as u32).encode_to(&mut encoded);
+        for log in header.digest.logs {
+            encoded.extend(hexstr_to_vec(log)?);
+        }
+        Ok(Self {
+            number: block_number::<R>(number)?,
+            hash: R::Hash::decode(&mut &sp_core::blake2_256(&encoded)[..])?,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn decode_storage<V: Decode>(hexstr: String) -> ApiResult<Option<V>> {
+    info!("storage hex = {}", hexstr);
+    let hexstr = hexstr
+        .trim_matches('\"')
+        .to_string()
+        .trim_start_matches("0x")
+        .to_string();
+    match hexstr.as_str() {
+        "null" => Ok(None),
+        _ => Ok(Some(Decode::decode(&mut &hex::decode(&hexstr)?[..])?)),
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use crate::test_support::fixtures::*;
+    use crate::test_support::{hex_prefixed, ScriptedStatus};
+    use codec::Compact;
+    use keyring::AccountKeyring;
+    use serde_json::json;
+    use sp_core::sr25519;
+    use sp_runtime::{generic::Header as GenericHeader, traits::BlakeTwo256, DispatchError};
+    use support::weights::DispatchInfo;
+
+    #[test]
+    fn mortal_era_starts_at_best_block() {
+        let node = node().with_best_block_number(100).spawn().unwrap();
+        let api = alice(&node);
+        let requests = node.requests().len();
+
+        assert_eq!(api.get_era().unwrap(), (Era::Immortal, api.genesis_hash));
+        assert_eq!(node.requests().len(), requests);
+
+        let api = api.set_era_period(64);
+        let (era, checkpoint) = api.get_era().unwrap();
+        let best: GenericHeader<u64, BlakeTwo256> =
+            serde_json::from_str(&api.get_header(None).unwrap()).unwrap();
+
+        assert_eq!(era, Era::mortal(64, 100));
+        assert_eq!(era.birth(100), 100);
+        assert_eq!(checkpoint, best.hash());
+
+        let xt = transfer(&api);
+        let (_, signature, extra) = xt.signature.as_ref().unwrap();
+        let additional_signed = (
+            api.runtime_version.spec_version,
+            api.genesis_hash,
+            checkpoint,
+            (),
+            (),
+            (),
+            (),
+        );
+        let payload = (&xt.function, extra, additional_signed).encode();
+        assert_eq!(extra.era(), era);
+        assert!(signature.verify(&payload[..], &AccountKeyring::Alice.to_account_id()));
+    }
+
+    #[test]
+    fn long_mortal_era_starts_at_a_quantized_block() {
+        let node = node().with_best_block_number(101).spawn().unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url())
+            .unwrap()
+            .set_era_period(8192);
+
+        let (era, checkpoint) = api.get_era().unwrap();
+
+        assert_eq!(era.birth(101), 100);
+        assert_eq!(checkpoint, Hash::from_low_u64_be(100));
+    }
+
+    #[test]
+    fn replacement_keeps_nonce_and_raises_tip() {
+        let node = node().spawn().unwrap();
+        let api = alice(&node).set_tip(5);
+        let xt = transfer(&api);
+
+        let replacement = api.compose_replacement(&xt, 10).unwrap();
+
+        let extra = &replacement.signature.as_ref().unwrap().2;
+        assert_eq!(xt.signature.as_ref().unwrap().2.tip(), 5);
+        assert_eq!(extra.tip(), 10);
+        assert_eq!(extra.nonce(), 0);
+        assert_eq!(replacement.function, xt.function);
+        match api.compose_replacement(&xt, 5) {
+            Err(ApiError::TipNotHigher { old, new }) => {
+                assert_eq!((old.as_str(), new.as_str()), ("5", "5"))
             }
+            other => panic!("unexpected result: {:?}", other.map(|xt| xt.hex_encode())),
+        }
+    }
+
+    #[test]
+    fn replacement_keeps_the_mortal_era() {
+        let node = node().with_best_block_number(100).spawn().unwrap();
+        let api = alice(&node).set_era_period(64);
+        let xt = transfer(&api);
+        let (_, checkpoint) = api.get_era().unwrap();
+
+        let replacement = api.compose_replacement(&xt, 10).unwrap();
+
+        let (_, signature, extra) = replacement.signature.as_ref().unwrap();
+        let additional_signed = (
+            api.runtime_version.spec_version,
+            api.genesis_hash,
+            checkpoint,
+            (),
+            (),
+            (),
+            (),
+        );
+        let payload = (&replacement.function, extra, additional_signed).encode();
+        assert_eq!(extra.era(), Era::mortal(64, 100));
+        assert!(signature.verify(&payload[..], &AccountKeyring::Alice.to_account_id()));
+    }
+
+    #[test]
+    fn replacement_of_another_signer_is_rejected() {
+        let node = node().spawn().unwrap();
+        let alice = alice(&node);
+        let bob = Api::new(node.url())
+            .unwrap()
+            .set_signer(AccountKeyring::Bob.pair());
+        let xt = transfer(&alice);
+
+        match bob.compose_replacement(&xt, 10) {
+            Err(ApiError::WrongSigner(_)) => {}
+            other => panic!("unexpected result: {:?}", other.map(|xt| xt.hex_encode())),
+        }
+    }
+
+    #[test]
+    fn extrinsic_without_outcome_event_is_reported_as_error() {
+        let node = node().spawn().unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url()).unwrap();
+
+        match api.send_extrinsic_with_report("0x0102".to_string(), XtStatus::InBlock) {
+            Err(ApiError::Events(EventsError::MissingExtrinsicOutcome(0))) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match api.get_extrinsic_report("0x03", Hash::repeat_byte(0x02)) {
+            Err(ApiError::ExtrinsicNotInBlock(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn successful_extrinsic_is_reported() {
+        let key = storage_key_hash("System", "Events", None);
+        let info = DispatchInfo::default();
+        // encoded `EventRecord`s of `ExtrinsicFailed` for the first and `ExtrinsicSuccess` for
+        // the second extrinsic. The system module has event index 0.
+        let events = [
+            (Phase::ApplyExtrinsic(0), 0u8, 1u8, DispatchError::BadOrigin).encode(),
+            info.encode(),
+            Vec::<Hash>::new().encode(),
+            (Phase::ApplyExtrinsic(1), 0u8, 0u8, info).encode(),
+            Vec::<Hash>::new().encode(),
+        ];
+        let mut records = Compact(2u32).encode();
+        records.extend(events.concat());
+        let node = node()
+            .with_storage(hex::decode(&key[2..]).unwrap(), records)
+            .spawn()
+            .unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url()).unwrap();
+        api.submit_extrinsic("0x01".to_string()).unwrap();
+
+        let report = api
+            .send_extrinsic_with_report("0x0102".to_string(), XtStatus::InBlock)
+            .unwrap();
+
+        assert_eq!(report.block_hash, Hash::repeat_byte(0x02));
+        assert_eq!(report.extrinsic_index, 1);
+        assert!(report.is_success());
+        assert_eq!(report.error_description(), None);
+        assert!(report.events.is_empty());
+    }
+
+    #[test]
+    fn extrinsic_with_future_nonce_is_not_reported() {
+        let node = node()
+            .with_extrinsic_statuses(vec![ScriptedStatus::Future])
+            .spawn()
+            .unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url()).unwrap();
+
+        match api.send_extrinsic_with_report("0x00".to_string(), XtStatus::InBlock) {
+            Err(ApiError::NotIncluded(TransactionStatus::Future)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn all_status_updates_are_watched() {
+        let block = Hash::repeat_byte(0x03);
+        let node = node()
+            .with_extrinsic_statuses(vec![
+                ScriptedStatus::Ready,
+                ScriptedStatus::Broadcast(vec!["QmPeer".into()]),
+                ScriptedStatus::InBlock(block),
+                ScriptedStatus::Retracted(block),
+                ScriptedStatus::Dropped,
+            ])
+            .spawn()
+            .unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url()).unwrap();
+
+        let statuses: Vec<TransactionStatus> = api
+            .watch_extrinsic("0x00".to_string())
+            .unwrap()
+            .map(|status| status.unwrap())
+            .collect();
+
+        assert_eq!(
+            statuses,
+            vec![
+                TransactionStatus::Ready,
+                TransactionStatus::Broadcast(vec!["QmPeer".into()]),
+                TransactionStatus::InBlock(block),
+                TransactionStatus::Retracted(block),
+                TransactionStatus::Dropped,
+            ]
+        );
+        match api.send_extrinsic("0x00".to_string(), XtStatus::Finalized) {
+            Err(ApiError::NotIncluded(TransactionStatus::Dropped)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn payment_info_is_decoded() {
+        let (node, api) = answering(
+            "payment_queryInfo",
+            json!({ "weight": 195_000_000, "class": "normal", "partialFee": 1_250_000 }),
+        );
+        let xt = transfer(&api);
+        let at = Hash::repeat_byte(0x02);
+
+        let info = api.get_payment_info(&xt, Some(at)).unwrap();
+
+        assert_eq!(info.weight, 195_000_000);
+        assert_eq!(info.class, support::weights::DispatchClass::Normal);
+        assert_eq!(info.partial_fee, 1_250_000);
+        assert_eq!(node.params_of("payment_queryInfo"), vec![json!([xt.hex_encode(), at])]);
+    }
+
+    #[test]
+    fn dry_run_result_is_decoded() {
+        let outcome: ApplyExtrinsicResult = Ok(Err(DispatchError::BadOrigin));
+        let (node, api) = answering("system_dryRun", json!(hex_prefixed(&outcome.encode())));
+        let xt = transfer(&api);
+        let at = Hash::repeat_byte(0x02);
+
+        let dry_run = api.dry_run(&xt, Some(at)).unwrap();
+
+        assert_eq!(dry_run.result, outcome);
+        assert_eq!(node.params_of("system_dryRun"), vec![json!([xt.hex_encode(), at])]);
+    }
+
+    #[test]
+    fn dry_run_fails_if_the_result_is_not_consumed() {
+        let outcome: ApplyExtrinsicResult = Ok(Ok(()));
+        let mut encoded = outcome.encode();
+        encoded.push(0);
+        let (_node, api) = answering("system_dryRun", json!(hex_prefixed(&encoded)));
+        let xt = transfer(&api);
+
+        match api.dry_run(&xt, None) {
+            Err(ApiError::Codec(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn state_call_decodes_the_result() {
+        let (node, api) = answering("state_call", json!(hex_prefixed(&5u32.encode())));
+        let alice = AccountKeyring::Alice.to_account_id();
+        let at = Hash::repeat_byte(0x02);
+
+        let nonce = api.call_account_nonce(&alice, Some(at)).unwrap();
+
+        assert_eq!(nonce, 5);
+        assert_eq!(
+            node.params_of("state_call"),
+            vec![json!(["AccountNonceApi_account_nonce", hex_prefixed(&alice.encode()), at])]
+        );
+    }
+
+    #[test]
+    fn state_call_fails_if_the_result_is_not_consumed() {
+        let (_node, api) = answering("state_call", json!(hex_prefixed(&5u64.encode())));
+
+        match api.call_account_nonce(&AccountKeyring::Alice.to_account_id(), None) {
+            Err(ApiError::Codec(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn runtime_metadata_and_version_are_called() {
+        let metadata = node_runtime::Runtime::metadata();
+        let (node, api) = answering("state_call", json!(hex_prefixed(&metadata.encode().encode())));
+
+        assert_eq!(api.call_metadata(None).unwrap().encode(), metadata.encode());
+
+        node.set_response("state_call", json!(hex_prefixed(&node_runtime::VERSION.encode())));
+        assert_eq!(api.call_core_version(None).unwrap(), node_runtime::VERSION);
+
+        assert_eq!(
+            node.params_of("state_call"),
+            vec![json!(["Metadata_metadata", "0x", null]), json!(["Core_version", "0x", null])]
+        );
+    }
+
+    #[test]
+    fn unanswered_request_times_out() {
+        let node = node().with_unanswered("chain_getHeader").spawn().unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url())
+            .unwrap()
+            .set_request_timeout(TIMEOUT);
+
+        match api.get_header(None) {
+            Err(ApiError::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        // the connection is still usable
+        assert_eq!(api.get_genesis_hash().unwrap(), Hash::repeat_byte(0x01));
+    }
+
+    #[test]
+    fn composing_fails_if_the_nonce_is_not_answered() {
+        let node = node().with_unanswered("state_getStorage").spawn().unwrap();
+        let api = alice(&node).set_request_timeout(TIMEOUT);
+
+        match api.balance_transfer(AccountKeyring::Bob.to_account_id(), 42) {
+            Err(ApiError::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+            other => panic!("unexpected result: {:?}", other.map(|xt| xt.hex_encode())),
+        }
+    }
+
+    #[test]
+    fn failing_signer_is_reported() {
+        let node = node().spawn().unwrap();
+        let api = Api::new(node.url()).unwrap().set_signer(UnpluggedSigner);
+
+        match api.compose_extrinsic_from_call(vec![4u8, 5, 6]) {
+            Err(ApiError::Signer(error)) => assert!(error.contains("unplugged")),
+            other => panic!("unexpected result: {:?}", other.map(|xt| xt.hex_encode())),
+        }
+    }
+
+    #[test]
+    fn block_number_beyond_the_json_rpc_is_rejected() {
+        let node = node().spawn().unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url()).unwrap();
+        let requests = node.requests().len();
+
+        match api.get_block_hash(Some(u64::from(u32::MAX) + 1)) {
+            Err(ApiError::BlockNumberOutOfRange(number)) => assert_eq!(number, 1 << 32),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(node.requests().len(), requests);
+    }
+
+    #[test]
+    fn extrinsic_that_is_not_included_times_out() {
+        let node = node()
+            .with_extrinsic_statuses(vec![ScriptedStatus::Ready])
+            .spawn()
+            .unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url())
+            .unwrap()
+            .set_finalization_timeout(TIMEOUT);
+
+        match api.send_extrinsic("0x00".to_string(), XtStatus::InBlock) {
+            Err(ApiError::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let unwatch = wait_for_request(&node, "author_unwatchExtrinsic");
+        assert_eq!(unwatch["params"][0], json!(1));
+    }
+
+    #[test]
+    fn watching_ends_with_a_timeout() {
+        let node = node()
+            .with_extrinsic_statuses(vec![ScriptedStatus::Ready])
+            .spawn()
+            .unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url())
+            .unwrap()
+            .set_finalization_timeout(TIMEOUT);
+
+        let mut statuses = api.watch_extrinsic("0x00".to_string()).unwrap();
+
+        assert_eq!(statuses.next().unwrap().unwrap(), TransactionStatus::Ready);
+        match statuses.next() {
+            Some(Err(ApiError::Timeout(timeout))) => assert_eq!(timeout, TIMEOUT),
+            other => panic!("unexpected status: {:?}", other),
+        }
+        assert!(statuses.next().is_none());
+        wait_for_request(&node, "author_unwatchExtrinsic");
+    }
+
+    #[test]
+    fn waiting_for_an_event_times_out() {
+        let node = node().spawn().unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url())
+            .unwrap()
+            .set_finalization_timeout(TIMEOUT);
+        let (_events_in, events_out) = channel();
+
+        match api.wait_for_raw_event("Balances", "Transfer", &events_out) {
+            Err(ApiError::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+            other => panic!("unexpected result: {:?}", other),
         }
     }
 }
@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU General Public License
 // along with substrate-subxt.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{collections::HashMap, convert::TryFrom, marker::PhantomData, str::FromStr};
+use std::{collections::HashMap, convert::TryFrom, fmt, marker::PhantomData, str::FromStr};
 
 use codec::{Decode, Encode};
 
@@ -25,6 +25,7 @@ use metadata::{
 };
 use serde::ser::Serialize;
 use sp_core::storage::StorageKey;
+use sp_runtime::DispatchError;
 use log::*;
 
 #[derive(Debug, thiserror::Error)]
@@ -36,9 +37,11 @@ pub enum MetadataError {
     #[error("Module with events not found")]
     ModuleWithEventsNotFound(u8),
     #[error("Call not found")]
-    CallNotFound(&'static str),
+    CallNotFound(String),
     #[error("Event not found")]
     EventNotFound(u8),
+    #[error("Error {1} of module {0} not found")]
+    ErrorNotFound(u8, u8),
     #[error("Storage not found")]
     StorageNotFound(&'static str),
     #[error("Storage type error")]
@@ -52,6 +55,8 @@ pub struct Metadata {
     modules: HashMap<String, ModuleMetadata>,
     modules_with_calls: HashMap<String, ModuleWithCalls>,
     modules_with_events: HashMap<String, ModuleWithEvents>,
+    /// Errors by module and error index, as in `DispatchError::Module`
+    module_errors: HashMap<(u8, u8), ModuleErrorMetadata>,
 }
 
 impl Metadata {
@@ -79,6 +84,32 @@ impl Metadata {
             .ok_or(MetadataError::ModuleNotFound(name))
     }
 
+    /// The call index `[module_index, call_index]` of `call` of `module`.
+    pub fn call_index<S, T>(&self, module: S, call: T) -> Result<[u8; 2], MetadataError>
+    where
+        S: ToString,
+        T: ToString,
+    {
+        let module = self.module_with_calls(module)?;
+        let call = call.to_string();
+        match module.calls.get(&call) {
+            Some(call_index) => Ok([module.index, *call_index]),
+            None => Err(MetadataError::CallNotFound(call)),
+        }
+    }
+
+    /// Names the module and call of the call index `[module_index, call_index]`.
+    pub fn call_name(&self, module_index: u8, call_index: u8) -> Option<(&str, &str)> {
+        let module = self
+            .modules_with_calls()
+            .find(|module| module.index == module_index)?;
+        module
+            .calls
+            .iter()
+            .find(|(_, &index)| index == call_index)
+            .map(|(name, _)| (module.name.as_str(), name.as_str()))
+    }
+
     pub fn modules_with_events(&self) -> impl Iterator<Item = &ModuleWithEvents> {
         self.modules_with_events.values()
     }
@@ -100,6 +131,34 @@ impl Metadata {
             .ok_or(MetadataError::ModuleWithEventsNotFound(module_index))
     }
 
+    pub fn module_errors(&self) -> impl Iterator<Item = &ModuleErrorMetadata> {
+        self.module_errors.values()
+    }
+
+    pub fn module_error(
+        &self,
+        module_index: u8,
+        error_index: u8,
+    ) -> Result<&ModuleErrorMetadata, MetadataError> {
+        self.module_errors
+            .get(&(module_index, error_index))
+            .ok_or(MetadataError::ErrorNotFound(module_index, error_index))
+    }
+
+    /// Looks up the name and documentation of a `DispatchError::Module`.
+    pub fn dispatch_error(&self, error: &DispatchError) -> Option<&ModuleErrorMetadata> {
+        match error {
+            DispatchError::Module { index, error, .. } => self.module_error(*index, *error).ok(),
+            _ => None,
+        }
+    }
+
+    /// Human readable `error`, e.g. `Balances::InsufficientBalance: Balance too low to send
+    /// value`.
+    pub fn describe_dispatch_error(&self, error: &DispatchError) -> String {
+        dispatch_error_description(error, self.dispatch_error(error))
+    }
+
     pub fn print_overview(&self) {
         let mut string = String::new();
         for (name, module) in &self.modules {
@@ -401,6 +460,42 @@ impl ModuleEventMetadata {
     }
 }
 
+/// An error a module can return, e.g. `Balances::InsufficientBalance`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleErrorMetadata {
+    pub module: String,
+    pub name: String,
+    pub documentation: Vec<String>,
+}
+
+impl ModuleErrorMetadata {
+    /// The name followed by the documentation, if any.
+    pub fn description(&self) -> String {
+        let docs = self.documentation.join(" ");
+        match docs.trim() {
+            "" => self.to_string(),
+            docs => format!("{}: {}", self, docs),
+        }
+    }
+}
+
+/// Human readable `error`, described by `module_error` if it was looked up.
+pub fn dispatch_error_description(
+    error: &DispatchError,
+    module_error: Option<&ModuleErrorMetadata>,
+) -> String {
+    match module_error {
+        Some(module_error) => module_error.description(),
+        None => format!("{:?}", error),
+    }
+}
+
+impl fmt::Display for ModuleErrorMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}::{}", self.module, self.name)
+    }
+}
+
 /// Naive representation of event argument types, supports current set of substrate EventArg types.
 /// If and when Substrate uses `type-metadata`, this can be replaced.
 ///
@@ -489,7 +584,9 @@ impl TryFrom<RuntimeMetadataPrefixed> for Metadata {
         let mut modules = HashMap::new();
         let mut modules_with_calls = HashMap::new();
         let mut modules_with_events = HashMap::new();
-        for module in convert(meta.modules)?.into_iter() {
+        let mut module_errors = HashMap::new();
+        // `DispatchError::Module` counts all modules, not only those with errors
+        for (module_index, module) in convert(meta.modules)?.into_iter().enumerate() {
             let module_name = convert(module.name.clone())?;
 
             let mut storage_map = HashMap::new();
@@ -540,11 +637,22 @@ impl TryFrom<RuntimeMetadataPrefixed> for Metadata {
                     },
                 );
             }
+            for (error_index, error) in convert(module.errors)?.into_iter().enumerate() {
+                module_errors.insert(
+                    (module_index as u8, error_index as u8),
+                    ModuleErrorMetadata {
+                        module: module_name.clone(),
+                        name: convert(error.name)?,
+                        documentation: convert(error.documentation)?,
+                    },
+                );
+            }
         }
         Ok(Metadata {
             modules,
             modules_with_calls,
             modules_with_events,
+            module_errors,
         })
     }
 }
@@ -580,3 +688,47 @@ fn convert_entry(
         default,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_errors_are_looked_up_by_index() {
+        let metadata = Metadata::try_from(node_runtime::Runtime::metadata()).unwrap();
+        // Balances is the 7th module in `construct_runtime!` of node_runtime, and
+        // InsufficientBalance the 4th variant of its `Error`
+        let dispatch_error = DispatchError::Module {
+            index: 6,
+            error: 3,
+            message: None,
+        };
+
+        let insufficient_balance = metadata.dispatch_error(&dispatch_error).unwrap();
+        assert_eq!(insufficient_balance.module, "Balances");
+        assert_eq!(insufficient_balance.name, "InsufficientBalance");
+        assert!(metadata
+            .describe_dispatch_error(&dispatch_error)
+            .starts_with("Balances::InsufficientBalance: "));
+        assert_eq!(
+            metadata.describe_dispatch_error(&DispatchError::BadOrigin),
+            "BadOrigin"
+        );
+    }
+
+    #[test]
+    fn calls_are_composed_from_the_metadata() {
+        let metadata = Metadata::try_from(node_runtime::Runtime::metadata()).unwrap();
+
+        let call = crate::compose_call!(metadata, "Balances", "transfer", 42u8).unwrap();
+        assert_eq!(call, ([6, 0], 42u8));
+        assert!(matches!(
+            crate::compose_call!(metadata, "Balances", "steal", 42u8),
+            Err(MetadataError::CallNotFound(call)) if call == "steal"
+        ));
+        assert!(matches!(
+            crate::compose_call!(metadata, "Bank", "transfer"),
+            Err(MetadataError::ModuleNotFound(module)) if module == "Bank"
+        ));
+    }
+}
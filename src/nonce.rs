@@ -0,0 +1,451 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Local nonces of a signer, for sending many extrinsics without waiting for them to be
+//! included. See `Api::enable_nonce_manager`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+
+use sp_core::blake2_256;
+
+use crate::ApiResult;
+
+/// What became of a sent extrinsic, as far as its nonce is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sent {
+    /// In the pool or in a block, its nonce is used.
+    Accepted,
+    /// In the pool, but waiting for a lower nonce that the node misses.
+    Future,
+    /// Rejected or dropped by the node, its nonce is unused.
+    Rejected,
+    /// Unknown, e.g. after a timeout or a lost connection.
+    Unknown,
+}
+
+/// Hands out consecutive nonces of one account. The first nonce is fetched from the node with
+/// `system_accountNextIndex`, which counts the extrinsics of the account in the transaction
+/// pool. Shared between threads, every nonce is handed out once, unless its extrinsic is
+/// rejected or lost.
+///
+/// A nonce is held from `next` until its extrinsic is `sent` or the nonce is released, and never
+/// handed out while held. If the local nonces may be off, e.g. after an extrinsic went to the
+/// future queue, they are checked against the node before the next is handed out. The nonce the
+/// node misses first and the nonces of lost extrinsics are then handed out again, unless held.
+#[derive(Debug)]
+pub struct NonceManager {
+    account: String,
+    nonces: Mutex<Nonces>,
+}
+
+#[derive(Debug, Default)]
+struct Nonces {
+    /// The nonce after the highest one handed out, `None` until fetched.
+    next: Option<u64>,
+    /// Nonces below `next` to hand out first, lowest first.
+    free: BTreeSet<u64>,
+    /// Nonces handed out whose extrinsics are not composed yet.
+    held: BTreeSet<u64>,
+    /// Nonces of composed extrinsics that are not sent yet, by the hash of the extrinsic.
+    pending: HashMap<[u8; 32], u64>,
+    /// Nonces of extrinsics that may not have reached the node, e.g. after a timeout.
+    lost: BTreeSet<u64>,
+    /// Whether to check with the node before handing out the next nonce.
+    stale: bool,
+    /// Counts the times the nonces became stale, to tell if they did during a fetch.
+    staled: u64,
+    /// Counts the fetches reconciled, a fetch is dropped if another one was reconciled meanwhile.
+    synced: u64,
+}
+
+impl Nonces {
+    fn is_held(&self, nonce: u64) -> bool {
+        self.held.contains(&nonce) || self.pending.values().any(|pending| *pending == nonce)
+    }
+
+    fn mark_stale(&mut self) {
+        self.stale = true;
+        self.staled += 1;
+    }
+
+    /// Reconciles with `fetched`, the next nonce of the node as of `staled`.
+    fn sync(&mut self, fetched: u64, staled: u64) {
+        self.synced += 1;
+        if self.staled == staled {
+            self.stale = false;
+        }
+        // everything below is in a block or in the pool
+        self.free = self.free.split_off(&fetched);
+        let lost = std::mem::take(&mut self.lost).split_off(&fetched);
+        match self.next {
+            Some(next) if fetched < next => {
+                let missed = std::iter::once(fetched).chain(lost);
+                for nonce in missed.filter(|nonce| *nonce < next) {
+                    if !self.is_held(nonce) {
+                        self.free.insert(nonce);
+                    }
+                }
+            }
+            _ => self.next = Some(fetched),
+        }
+    }
+
+    fn take(&mut self) -> u64 {
+        let nonce = match self.free.iter().next().cloned() {
+            Some(nonce) => {
+                self.free.remove(&nonce);
+                nonce
+            }
+            None => {
+                let nonce = self.next.unwrap_or_default();
+                self.next = Some(nonce + 1);
+                nonce
+            }
+        };
+        self.held.insert(nonce);
+        nonce
+    }
+}
+
+impl NonceManager {
+    /// The nonces of `account`, the SS58 address as expected by `system_accountNextIndex`.
+    pub fn new(account: String) -> Self {
+        NonceManager {
+            account,
+            nonces: Mutex::new(Nonces::default()),
+        }
+    }
+
+    pub fn account(&self) -> &str {
+        &self.account
+    }
+
+    /// Hands out and holds the next nonce, until it is `track`ed or `release`d. If there is none
+    /// yet, or the nonces may be off, the next one of the node is fetched with `seed` first. The
+    /// nonces are not locked during the fetch.
+    pub fn next<F>(&self, seed: F) -> ApiResult<u64>
+    where
+        F: FnOnce(&str) -> ApiResult<u64>,
+    {
+        let fetch = {
+            let nonces = self.nonces.lock().unwrap();
+            match nonces.next {
+                Some(_) if !nonces.stale => None,
+                _ => Some((nonces.staled, nonces.synced)),
+            }
+        };
+        if let Some((staled, synced)) = fetch {
+            let fetched = seed(&self.account)?;
+            let mut nonces = self.nonces.lock().unwrap();
+            if nonces.synced == synced {
+                nonces.sync(fetched, staled);
+            }
+            return Ok(nonces.take());
+        }
+        Ok(self.nonces.lock().unwrap().take())
+    }
+
+    /// Remembers that `xt`, e.g. the encoded extrinsic, was composed with the held `nonce`, until
+    /// it is `sent`.
+    pub fn track(&self, xt: &[u8], nonce: u64) {
+        let mut nonces = self.nonces.lock().unwrap();
+        nonces.held.remove(&nonce);
+        nonces.pending.insert(blake2_256(xt), nonce);
+    }
+
+    /// Tracks the nonce tracked for `from` for `to` instead, e.g. for the extrinsic built from a
+    /// signing request.
+    pub fn retrack(&self, from: &[u8], to: &[u8]) {
+        let mut nonces = self.nonces.lock().unwrap();
+        if let Some(nonce) = nonces.pending.remove(&blake2_256(from)) {
+            nonces.pending.insert(blake2_256(to), nonce);
+        }
+    }
+
+    /// Hands out the held `nonce` again, e.g. because signing its extrinsic failed.
+    pub fn release(&self, nonce: u64) {
+        let mut nonces = self.nonces.lock().unwrap();
+        nonces.held.remove(&nonce);
+        nonces.free.insert(nonce);
+    }
+
+    /// Learns from what became of the tracked `xt`. If it was rejected, its nonce is handed out
+    /// again. Unless it was accepted, the nonces are checked against the node before the next is
+    /// handed out.
+    pub fn sent(&self, xt: &[u8], sent: Sent) {
+        let mut nonces = self.nonces.lock().unwrap();
+        let nonce = nonces.pending.remove(&blake2_256(xt));
+        match (sent, nonce) {
+            (Sent::Accepted, _) => return,
+            (Sent::Rejected, Some(nonce)) => {
+                nonces.free.insert(nonce);
+            }
+            (Sent::Unknown, Some(nonce)) => {
+                nonces.lost.insert(nonce);
+            }
+            _ => {}
+        }
+        nonces.mark_stale();
+    }
+
+    /// Checks the nonces against the node before the next is handed out.
+    pub fn resync(&self) {
+        self.nonces.lock().unwrap().mark_stale();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn nonces_are_handed_out_once_across_threads() {
+        let nonces = Arc::new(NonceManager::new("alice".into()));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let nonces = nonces.clone();
+                thread::spawn(move || {
+                    (0..25)
+                        .map(|_| nonces.next(|_| Ok(10)).unwrap())
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let handed_out: HashSet<u64> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert_eq!(handed_out, (10..110).collect());
+    }
+
+    #[test]
+    fn nonce_of_a_rejected_extrinsic_is_handed_out_again() {
+        let nonces = NonceManager::new("alice".into());
+        for (xt, nonce) in [b"a", b"b", b"c"].iter().zip(3..) {
+            assert_eq!(nonces.next(|_| Ok(3)).unwrap(), nonce);
+            nonces.track(&xt[..], nonce);
+        }
+
+        nonces.sent(b"a", Sent::Rejected);
+
+        // the other threads still hold 4 and 5
+        assert_eq!(nonces.next(|_| Ok(3)).unwrap(), 3);
+        assert_eq!(nonces.next(|_| panic!("no resync")).unwrap(), 6);
+    }
+
+    #[test]
+    fn nonce_the_node_misses_is_handed_out_again_unless_it_is_pending() {
+        let nonces = NonceManager::new("alice".into());
+        for (xt, nonce) in [b"a", b"b", b"c"].iter().zip(3..) {
+            assert_eq!(nonces.next(|_| Ok(3)).unwrap(), nonce);
+            nonces.track(&xt[..], nonce);
+        }
+
+        // c went to the future queue, the node misses 4, but b is not sent yet
+        nonces.sent(b"a", Sent::Accepted);
+        nonces.sent(b"c", Sent::Future);
+        assert_eq!(nonces.next(|_| Ok(4)).unwrap(), 6);
+
+        // b was lost, then a timeout makes the nonces stale again
+        nonces.sent(b"b", Sent::Unknown);
+        assert_eq!(nonces.next(|_| Ok(4)).unwrap(), 4);
+        assert_eq!(nonces.next(|_| panic!("no resync")).unwrap(), 7);
+    }
+
+    #[test]
+    fn nonce_handed_out_but_not_tracked_is_not_handed_out_again() {
+        let nonces = NonceManager::new("alice".into());
+        assert_eq!(nonces.next(|_| Ok(3)).unwrap(), 3);
+        assert_eq!(nonces.next(|_| Ok(3)).unwrap(), 4);
+        nonces.track(b"b", 4);
+
+        // b went to the future queue while 3 is still being signed
+        nonces.sent(b"b", Sent::Future);
+        assert_eq!(nonces.next(|_| Ok(3)).unwrap(), 5);
+
+        nonces.track(b"a", 3);
+        nonces.sent(b"a", Sent::Accepted);
+        assert_eq!(nonces.next(|_| panic!("no resync")).unwrap(), 6);
+    }
+
+    #[test]
+    fn every_nonce_the_node_misses_is_handed_out_again() {
+        let nonces = NonceManager::new("alice".into());
+        for (xt, nonce) in [b"a", b"b", b"c", b"d"].iter().zip(3..) {
+            assert_eq!(nonces.next(|_| Ok(3)).unwrap(), nonce);
+            nonces.track(&xt[..], nonce);
+        }
+
+        // a and c timed out and never reached the node, d went to the future queue
+        nonces.sent(b"a", Sent::Unknown);
+        nonces.sent(b"b", Sent::Accepted);
+        nonces.sent(b"c", Sent::Unknown);
+        nonces.sent(b"d", Sent::Future);
+
+        assert_eq!(nonces.next(|_| Ok(3)).unwrap(), 3);
+        assert_eq!(nonces.next(|_| panic!("no resync")).unwrap(), 5);
+        assert_eq!(nonces.next(|_| panic!("no resync")).unwrap(), 7);
+    }
+
+    #[test]
+    fn nonces_are_not_locked_while_fetching() {
+        let nonces = NonceManager::new("alice".into());
+        let nonce = nonces.next(|_| {
+            assert!(nonces.nonces.try_lock().is_ok());
+            Ok(3)
+        });
+        assert_eq!(nonce.unwrap(), 3);
+    }
+
+    #[test]
+    fn resync_fetches_the_nonce_again() {
+        let nonces = NonceManager::new("alice".into());
+        assert_eq!(nonces.next(|_| Ok(3)).unwrap(), 3);
+        assert_eq!(nonces.next(|_| Ok(0)).unwrap(), 4);
+
+        nonces.resync();
+        let nonce = nonces.next(|account| {
+            assert_eq!(account, "alice");
+            Ok(7)
+        });
+        assert_eq!(nonce.unwrap(), 7);
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod api_tests {
+    use crate::test_support::fixtures::*;
+    use crate::test_support::{FakeNodeHandle, ScriptedStatus};
+    use crate::extrinsic::xt_primitives::UncheckedExtrinsicV4;
+    use crate::{Api, ApiError, XtStatus};
+    use keyring::AccountKeyring;
+    use serde_json::json;
+    use sp_core::crypto::Ss58Codec;
+    use sp_core::sr25519;
+
+    fn managed_api(node: &FakeNodeHandle) -> Api<sr25519::Pair> {
+        alice(node).enable_nonce_manager().unwrap()
+    }
+
+    #[test]
+    fn nonce_manager_counts_from_the_next_index_of_the_node() {
+        let node = node()
+            .with_response("system_accountNextIndex", json!(5))
+            .spawn()
+            .unwrap();
+        let api = managed_api(&node);
+
+        let (first, second) = (transfer(&api), transfer(&api));
+
+        assert_eq!((nonce_of(&first), nonce_of(&second)), (5, 6));
+        assert_eq!(
+            node.params_of("system_accountNextIndex"),
+            vec![json!([AccountKeyring::Alice.to_account_id().to_ss58check()])]
+        );
+    }
+
+    #[test]
+    fn nonce_of_a_rejected_extrinsic_is_used_again() {
+        let node = node()
+            .with_response("system_accountNextIndex", json!(5))
+            .with_error("author_submitExtrinsic", 1010, "Invalid Transaction")
+            .with_error("author_submitAndWatchExtrinsic", 1010, "Invalid Transaction")
+            .spawn()
+            .unwrap();
+        let api = managed_api(&node);
+
+        let xt = transfer(&api);
+        match api.send_extrinsic(xt.hex_encode(), XtStatus::InBlock) {
+            Err(ApiError::JsonRpc(error)) => assert_eq!(error.code, 1010),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let xt = transfer(&api);
+        assert_eq!(nonce_of(&xt), 5);
+        match api.submit_extrinsic(xt.hex_encode()) {
+            Err(ApiError::JsonRpc(error)) => assert_eq!(error.code, 1010),
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert_eq!(nonce_of(&transfer(&api)), 5);
+        assert_eq!(node.requests_of("system_accountNextIndex").len(), 3);
+    }
+
+    #[test]
+    fn nonce_the_node_misses_is_used_after_a_future_extrinsic() {
+        let node = node()
+            .with_response("system_accountNextIndex", json!(5))
+            .with_extrinsic_statuses(vec![ScriptedStatus::Future])
+            .spawn()
+            .unwrap();
+        let api = managed_api(&node);
+        let pending = transfer(&api);
+        let xt = transfer(&api);
+
+        assert_eq!(api.send_extrinsic(xt.hex_encode(), XtStatus::InBlock).unwrap(), None);
+        // the node lost the extrinsic with nonce 4
+        node.set_response("system_accountNextIndex", json!(4));
+
+        let (filler, next) = (transfer(&api), transfer(&api));
+        assert_eq!((nonce_of(&pending), nonce_of(&xt)), (5, 6));
+        assert_eq!((nonce_of(&filler), nonce_of(&next)), (4, 7));
+        assert_eq!(node.requests_of("system_accountNextIndex").len(), 2);
+    }
+
+    #[test]
+    fn nonce_of_a_failed_signature_is_used_again() {
+        let node = node()
+            .with_response("system_accountNextIndex", json!(5))
+            .spawn()
+            .unwrap();
+        let api = Api::new(node.url())
+            .unwrap()
+            .set_signer(UnpluggedSigner)
+            .enable_nonce_manager()
+            .unwrap();
+
+        assert!(api.compose_extrinsic_from_call(vec![4u8]).is_err());
+        let nonces = api.nonce_manager.as_ref().unwrap();
+        assert_eq!(nonces.next(|_| panic!("no resync")).unwrap(), 5);
+    }
+
+    #[test]
+    fn signing_request_of_the_signer_holds_a_managed_nonce() {
+        let node = node()
+            .with_response("system_accountNextIndex", json!(5))
+            .spawn()
+            .unwrap();
+        let api = managed_api(&node);
+        let alice = AccountKeyring::Alice.to_account_id();
+
+        let request = api.compose_signing_request(&alice, vec![4u8]).unwrap();
+        let abandoned = api.compose_signing_request(&alice, vec![5u8]).unwrap();
+        assert_eq!(nonce_of(&transfer(&api)), 7);
+
+        let response = request.sign(&AccountKeyring::Alice.pair()).unwrap();
+        let xt: UncheckedExtrinsicV4<Vec<u8>> = api
+            .import_signing_response(&request, &request.request_hash, &response)
+            .unwrap();
+        assert_eq!(nonce_of(&xt), 5);
+        api.release_signing_request(&abandoned);
+        assert_eq!(nonce_of(&transfer(&api)), 6);
+    }
+}
@@ -0,0 +1,377 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Signing on an air-gapped machine. The online `Api` exports a `SigningRequest` with
+//! `Api::compose_signing_request`, the offline machine signs it with `SigningRequest::sign`
+//! without node access, and the online machine builds the extrinsic from the `SigningResponse`
+//! with `SigningRequest::into_extrinsic`. Both travel as json files.
+//!
+//! The request carries the `blake2_256` hash of its SCALE encoded description, call, extra and
+//! additional signed data, so no bytes can be moved from one field to another. The online machine
+//! keeps the hash it exported, because the files may be altered on their way. Signing checks the
+//! request against its hash, importing checks it against the kept one and verifies the signature.
+
+use std::fs;
+use std::path::Path;
+
+use codec::{DecodeAll, Encode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sp_core::blake2_256;
+use sp_runtime::traits::Verify;
+use sp_runtime::{AccountId32, MultiSignature};
+
+use crate::extrinsic::xt_primitives::{
+    ExtraParamsOf, SignedExtra, SignedExtraOf, UncheckedExtrinsicV4,
+};
+use crate::runtime::Runtime;
+use crate::signer::Signer;
+
+pub type OfflineSigningResult<T> = Result<T, OfflineSigningError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OfflineSigningError {
+    #[error("Could not access file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid json: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid hex: {0}")]
+    Hex(#[from] hex::FromHexError),
+    #[error("Scale codec error: {0}")]
+    Codec(#[from] codec::Error),
+    #[error("Request does not match the hash {0}, it was altered")]
+    RequestAltered(String),
+    #[error("Response signs request {0}, not the exported one")]
+    OtherRequest(String),
+    #[error("Request is for signer {expected}, not {found}")]
+    WrongSigner { expected: String, found: String },
+    #[error("Signer failed: {0}")]
+    Signer(String),
+    #[error("Signature does not match the payload and signer")]
+    InvalidSignature,
+}
+
+/// An unsigned `SignedPayload` with its signer. All encoded fields are 0x prefixed hex.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningRequest {
+    /// What the call does, e.g. `Balances::transfer`, for the person approving it offline.
+    pub description: String,
+    /// The encoded account id of the signer.
+    pub signer: String,
+    pub call: String,
+    /// The encoded `SignedExtra::Extra`, e.g. era, nonce and tip.
+    pub extra: String,
+    /// The encoded `SignedExtra::AdditionalSigned`, e.g. spec version and genesis hash.
+    pub additional_signed: String,
+    /// The `blake2_256` hash of the encoded description, call, extra and additional signed.
+    pub request_hash: String,
+}
+
+/// The signature of a `SigningRequest`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SigningResponse {
+    /// The `request_hash` of the signed request.
+    pub request_hash: String,
+    /// The encoded `MultiSignature`.
+    pub signature: String,
+}
+
+impl SigningRequest {
+    /// Prepares signing `call` by `signer` for runtime `R`. See `Api::compose_signing_request`
+    /// for a request with the current nonce, era and tip.
+    pub fn new<Call, R>(
+        description: String,
+        signer: &R::AccountId,
+        call: &Call,
+        params: &ExtraParamsOf<R::SignedExtra>,
+    ) -> Self
+    where
+        Call: Encode,
+        R: Runtime,
+    {
+        let payload = [
+            call.encode(),
+            R::SignedExtra::extra(params).encode(),
+            R::SignedExtra::additional_signed(params).encode(),
+        ];
+        SigningRequest {
+            request_hash: request_hash(&description, &payload[0], &payload[1], &payload[2]),
+            description,
+            signer: to_hex(&signer.encode()),
+            call: to_hex(&payload[0]),
+            extra: to_hex(&payload[1]),
+            additional_signed: to_hex(&payload[2]),
+        }
+    }
+
+    /// The bytes to sign, like `SignedPayload::using_encoded`. Fails if the request does not
+    /// match `request_hash`.
+    pub fn payload(&self) -> OfflineSigningResult<Vec<u8>> {
+        if self.hash()? != self.request_hash.to_lowercase() {
+            return Err(OfflineSigningError::RequestAltered(self.request_hash.clone()));
+        }
+        let payload = self.raw_payload()?;
+        if payload.len() > 256 {
+            Ok(blake2_256(&payload).to_vec())
+        } else {
+            Ok(payload)
+        }
+    }
+
+    fn raw_payload(&self) -> OfflineSigningResult<Vec<u8>> {
+        Ok(self.fields()?.concat())
+    }
+
+    /// The decoded call, extra and additional signed data.
+    fn fields(&self) -> OfflineSigningResult<[Vec<u8>; 3]> {
+        Ok([
+            from_hex(&self.call)?,
+            from_hex(&self.extra)?,
+            from_hex(&self.additional_signed)?,
+        ])
+    }
+
+    /// The `request_hash` of the current fields.
+    fn hash(&self) -> OfflineSigningResult<String> {
+        let [call, extra, additional_signed] = self.fields()?;
+        Ok(request_hash(&self.description, &call, &extra, &additional_signed))
+    }
+
+    /// Signs the request on the offline machine, `signer` must be the signer of the request.
+    pub fn sign<AccountId, S>(&self, signer: &S) -> OfflineSigningResult<SigningResponse>
+    where
+        AccountId: Encode,
+        S: Signer<AccountId> + ?Sized,
+    {
+        let found = to_hex(&signer.account_id().encode());
+        if found != self.signer.to_lowercase() {
+            return Err(OfflineSigningError::WrongSigner {
+                expected: self.signer.clone(),
+                found,
+            });
+        }
+        let signature = signer
+            .sign(&self.payload()?)
+            .map_err(|e| OfflineSigningError::Signer(format!("{:?}", e)))?;
+        Ok(SigningResponse {
+            request_hash: self.request_hash.clone(),
+            signature: to_hex(&signature.encode()),
+        })
+    }
+
+    /// Builds the signed extrinsic from the `response` to this request. `request_hash` is the
+    /// hash of the exported request, kept on the online machine. Checks that request and response
+    /// match it and that the signature is valid.
+    pub fn into_extrinsic<Call, R>(
+        &self,
+        request_hash: &str,
+        response: &SigningResponse,
+    ) -> OfflineSigningResult<UncheckedExtrinsicV4<Call, R>>
+    where
+        Call: Encode + DecodeAll,
+        R: Runtime<AccountId = AccountId32>,
+        SignedExtraOf<R>: DecodeAll,
+    {
+        let request_hash = request_hash.to_lowercase();
+        if self.request_hash.to_lowercase() != request_hash {
+            return Err(OfflineSigningError::RequestAltered(request_hash));
+        }
+        if response.request_hash.to_lowercase() != request_hash {
+            return Err(OfflineSigningError::OtherRequest(response.request_hash.clone()));
+        }
+        let payload = self.payload()?;
+        let signer = AccountId32::decode_all(&from_hex(&self.signer)?)?;
+        let signature = MultiSignature::decode_all(&from_hex(&response.signature)?)?;
+        if !signature.verify(payload.as_slice(), &signer) {
+            return Err(OfflineSigningError::InvalidSignature);
+        }
+        Ok(UncheckedExtrinsicV4::new_signed(
+            Call::decode_all(&from_hex(&self.call)?)?,
+            signer.into(),
+            signature,
+            SignedExtraOf::<R>::decode_all(&from_hex(&self.extra)?)?,
+        ))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> OfflineSigningResult<()> {
+        save(self, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> OfflineSigningResult<Self> {
+        load(path)
+    }
+}
+
+impl SigningResponse {
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> OfflineSigningResult<()> {
+        save(self, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> OfflineSigningResult<Self> {
+        load(path)
+    }
+}
+
+fn save<T: Serialize, P: AsRef<Path>>(value: &T, path: P) -> OfflineSigningResult<()> {
+    Ok(fs::write(path, serde_json::to_string_pretty(value)?)?)
+}
+
+fn load<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> OfflineSigningResult<T> {
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn request_hash(description: &str, call: &[u8], extra: &[u8], additional_signed: &[u8]) -> String {
+    to_hex(&blake2_256(&(description, call, extra, additional_signed).encode()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+fn from_hex(hexstr: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    hex::decode(hexstr.trim_start_matches("0x"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extrinsic::xt_primitives::{Era, ExtraParams, SignedPayload};
+    use crate::runtime::DefaultRuntime;
+    use keyring::AccountKeyring;
+    use sp_core::H256;
+
+    fn request() -> (SigningRequest, Vec<u8>) {
+        let params = ExtraParams {
+            nonce: 7,
+            tip: 0,
+            era: Era::Immortal,
+            era_checkpoint: H256::repeat_byte(1),
+            genesis_hash: H256::repeat_byte(1),
+            spec_version: 1,
+            custom: (),
+        };
+        let call = vec![4u8, 5, 6];
+        let request = SigningRequest::new::<_, DefaultRuntime>(
+            "Test::call".into(),
+            &AccountKeyring::Alice.to_account_id(),
+            &call,
+            &params,
+        );
+        let payload = SignedPayload::<_>::new(call, &params).using_encoded(|p| p.to_vec());
+        (request, payload)
+    }
+
+    #[test]
+    fn signed_request_becomes_extrinsic() {
+        let (request, payload) = request();
+        assert_eq!(request.payload().unwrap(), payload);
+
+        let response = request.sign(&AccountKeyring::Alice.pair()).unwrap();
+        let xt: UncheckedExtrinsicV4<Vec<u8>> = request
+            .into_extrinsic(&request.request_hash, &response)
+            .unwrap();
+
+        assert_eq!(xt.function, vec![4u8, 5, 6]);
+        assert_eq!(xt.signature.unwrap().0, AccountKeyring::Alice.to_account_id());
+    }
+
+    #[test]
+    fn altered_request_is_rejected() {
+        let (request, _) = request();
+        let response = request.sign(&AccountKeyring::Alice.pair()).unwrap();
+
+        let mut altered = request.clone();
+        altered.call = to_hex(&[4u8, 5, 7]);
+        assert!(matches!(
+            altered.sign(&AccountKeyring::Alice.pair()),
+            Err(OfflineSigningError::RequestAltered(_))
+        ));
+        // the bytes of the payload stay the same, but not their fields
+        let mut altered = request.clone();
+        altered.call = to_hex(&[3u8 << 2, 4, 5]);
+        altered.extra = format!("0x06{}", request.extra.trim_start_matches("0x"));
+        assert!(matches!(
+            altered.sign(&AccountKeyring::Alice.pair()),
+            Err(OfflineSigningError::RequestAltered(_))
+        ));
+        let mut altered = request.clone();
+        altered.description = "Test::harmless".into();
+        assert!(matches!(
+            altered.sign(&AccountKeyring::Alice.pair()),
+            Err(OfflineSigningError::RequestAltered(_))
+        ));
+        assert!(matches!(
+            request.sign(&AccountKeyring::Bob.pair()),
+            Err(OfflineSigningError::WrongSigner { .. })
+        ));
+
+        let forged = SigningResponse {
+            signature: to_hex(&MultiSignature::from(AccountKeyring::Alice.sign(b"other")).encode()),
+            ..response
+        };
+        assert!(matches!(
+            request.into_extrinsic::<Vec<u8>, DefaultRuntime>(&request.request_hash, &forged),
+            Err(OfflineSigningError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn request_rewritten_with_its_hash_is_rejected() {
+        let (request, _) = request();
+        let exported_hash = request.request_hash.clone();
+
+        let mut altered = request.clone();
+        altered.call = to_hex(&[4u8, 5, 7]);
+        altered.request_hash = altered.hash().unwrap();
+        // the offline machine can not tell, but the online one kept the exported hash
+        let response = altered.sign(&AccountKeyring::Alice.pair()).unwrap();
+
+        assert!(matches!(
+            altered.into_extrinsic::<Vec<u8>, DefaultRuntime>(&exported_hash, &response),
+            Err(OfflineSigningError::RequestAltered(_))
+        ));
+        assert!(matches!(
+            request.into_extrinsic::<Vec<u8>, DefaultRuntime>(&exported_hash, &response),
+            Err(OfflineSigningError::OtherRequest(_))
+        ));
+    }
+
+    #[test]
+    fn call_with_trailing_bytes_is_rejected() {
+        let (request, _) = request();
+        let params = ExtraParams {
+            nonce: 7,
+            tip: 0,
+            era: Era::Immortal,
+            era_checkpoint: H256::repeat_byte(1),
+            genesis_hash: H256::repeat_byte(1),
+            spec_version: 1,
+            custom: (),
+        };
+        let trailing = SigningRequest::new::<_, DefaultRuntime>(
+            request.description,
+            &AccountKeyring::Alice.to_account_id(),
+            &(vec![4u8, 5, 6], 0u8),
+            &params,
+        );
+        let response = trailing.sign(&AccountKeyring::Alice.pair()).unwrap();
+
+        assert!(matches!(
+            trailing.into_extrinsic::<Vec<u8>, DefaultRuntime>(&trailing.request_hash, &response),
+            Err(OfflineSigningError::Codec(_))
+        ));
+    }
+}
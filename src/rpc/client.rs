@@ -15,58 +15,341 @@
 
 */
 
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::mpsc::Sender as ThreadOut;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-use crate::rpc::json_req::REQUEST_TRANSFER;
+#[cfg(feature = "async")]
+use futures::channel::mpsc::UnboundedSender;
 use log::{debug, error, info, warn};
+use serde::Deserialize;
+use serde_json::Value;
 use ws::{CloseCode, Handler, Handshake, Message, Result, Sender};
 
+use crate::rpc::{json_req, JsonRpcError, RpcError, RpcResult};
+use crate::Hash;
+
 #[derive(Debug, PartialEq)]
 pub enum XtStatus {
     Finalized,
+    InBlock,
+    Broadcast,
     Ready,
     Future,
     Error,
     Unknown,
 }
 
-pub type OnMessageFn = fn(msg: Message, out: Sender, result: ThreadOut<String>) -> Result<()>;
+/// Status of a submitted extrinsic, as sent by the node with every `author_extrinsicUpdate`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransactionStatus {
+    /// In the pool, but not yet valid, e.g. because of a nonce gap.
+    Future,
+    /// In the pool and ready to be included in a block.
+    Ready,
+    /// Sent to the listed peers.
+    Broadcast(Vec<String>),
+    /// Included in the block with this hash.
+    InBlock(Hash),
+    /// The block it was included in has been retracted.
+    Retracted(Hash),
+    /// The block it was included in has not been finalized in time. The node stops watching it.
+    FinalityTimeout(Hash),
+    /// Included in the finalized block with this hash.
+    Finalized(Hash),
+    /// Replaced by the extrinsic with this hash, e.g. with the same nonce and a higher tip.
+    Usurped(Hash),
+    /// Dropped from the pool, e.g. because it was full.
+    Dropped,
+    /// No longer valid, e.g. because its nonce has been used.
+    Invalid,
+}
+
+impl TransactionStatus {
+    /// Whether the node sends no further updates after this one.
+    pub fn is_final(&self) -> bool {
+        match self {
+            TransactionStatus::Finalized(_)
+            | TransactionStatus::FinalityTimeout(_)
+            | TransactionStatus::Usurped(_)
+            | TransactionStatus::Dropped
+            | TransactionStatus::Invalid => true,
+            _ => false,
+        }
+    }
+}
 
+/// Handles a message that belongs to a request or subscription and forwards the relevant part
+/// to `result`. Returns `true` once no further messages are expected for it.
+pub type OnMessageFn = fn(msg: &str, result: &ResultSender) -> bool;
+
+/// Sending end of the channel the results of a request or subscription are delivered to.
+#[derive(Clone)]
+pub enum ResultSender {
+    /// Results are received by a thread.
+    Thread(ThreadOut<String>),
+    /// Results are received as `Stream`.
+    #[cfg(feature = "async")]
+    Async(UnboundedSender<String>),
+}
+
+impl ResultSender {
+    /// Returns the message back if the receiving end has hung up.
+    pub fn send(&self, msg: String) -> std::result::Result<(), String> {
+        match self {
+            ResultSender::Thread(sender) => sender.send(msg).map_err(|e| e.0),
+            #[cfg(feature = "async")]
+            ResultSender::Async(sender) => sender.unbounded_send(msg).map_err(|e| e.into_inner()),
+        }
+    }
+}
+
+impl From<ThreadOut<String>> for ResultSender {
+    fn from(sender: ThreadOut<String>) -> Self {
+        ResultSender::Thread(sender)
+    }
+}
+
+#[cfg(feature = "async")]
+impl From<UnboundedSender<String>> for ResultSender {
+    fn from(sender: UnboundedSender<String>) -> Self {
+        ResultSender::Async(sender)
+    }
+}
+
+/// A request that has been sent to the node and waits for its response.
+pub struct PendingRequest {
+    /// JSON-RPC id the request was first sent with. It identifies the request, and the
+    /// subscription it has started, even after being resent on a new connection.
+    pub id: String,
+    pub method: String,
+    /// The request as sent to the node, to start a subscription again after reconnecting.
+    pub jsonreq: Value,
+    pub result: ResultSender,
+    pub on_message_fn: OnMessageFn,
+}
+
+/// An active subscription, keyed by the subscription id the node assigned to it.
+pub type Subscriptions = Arc<Mutex<HashMap<String, PendingRequest>>>;
+/// Requests waiting for a response, keyed by the JSON-RPC id they were last sent with.
+pub type PendingRequests = Arc<Mutex<HashMap<String, PendingRequest>>>;
+/// Sender of the current connection, `None` while the connection is lost.
+pub type Connection = Arc<Mutex<Option<Sender>>>;
+/// Notified with the url of the node whenever the connection has been re-established.
+pub type ReconnectListeners = Arc<Mutex<Vec<ResultSender>>>;
+
+/// Handler of a websocket connection. Routes responses to their request by the JSON-RPC `id`
+/// and notifications to their subscription by the subscription id.
 pub struct RpcClient {
+    pub url: String,
     pub out: Sender,
-    pub request: String,
-    pub result: ThreadOut<String>,
-    pub on_message_fn: OnMessageFn,
+    pub ready: ThreadOut<()>,
+    pub connection: Connection,
+    pub next_id: Arc<AtomicU32>,
+    pub requests: PendingRequests,
+    pub subscriptions: Subscriptions,
+    pub reconnect_listeners: ReconnectListeners,
+    /// Whether this connection replaces one that was lost.
+    pub reconnected: bool,
+    /// Set once the connection has been established, `connection` is reset when it is closed.
+    pub opened: Arc<AtomicBool>,
+    /// Set once all clients are dropped, the connection is closed then.
+    pub closed: Arc<AtomicBool>,
 }
 
 impl Handler for RpcClient {
     fn on_open(&mut self, _: Handshake) -> Result<()> {
-        info!("sending request: {}", self.request);
-        self.out.send(self.request.clone()).unwrap();
+        info!("websocket connection to {} established", self.url);
+        // resume before new requests can be sent, so they are not sent twice
+        self.resume_subscriptions()?;
+        *self.connection.lock().unwrap() = Some(self.out.clone());
+        self.opened.store(true, Ordering::SeqCst);
+        if self.closed.load(Ordering::SeqCst) {
+            // all clients were dropped while connecting
+            return self.out.close(CloseCode::Normal);
+        }
+        if self.reconnected {
+            let url = self.url.clone();
+            self.reconnect_listeners
+                .lock()
+                .unwrap()
+                .retain(|listener| listener.send(url.clone()).is_ok());
+        }
+        // only `WsRpcClient::new` waits for the first connection
+        let _ = self.ready.send(());
         Ok(())
     }
 
     fn on_message(&mut self, msg: Message) -> Result<()> {
-        (self.on_message_fn)(msg, self.out.clone(), self.result.clone())
+        let retstr = msg.as_text()?;
+        debug!("got message {}", retstr);
+        let value: Value = match serde_json::from_str(retstr) {
+            Ok(value) => value,
+            Err(e) => {
+                error!("could not parse message from node: {:?}", e);
+                return Ok(());
+            }
+        };
+
+        if let Value::Array(responses) = &value {
+            // response to a batch request
+            for response in responses {
+                if let Some(id) = request_id(&response["id"]) {
+                    self.on_response(id, &response.to_string(), response)?;
+                }
+            }
+            Ok(())
+        } else if let Some(id) = request_id(&value["id"]) {
+            self.on_response(id, retstr, &value)
+        } else if let Some(subscription) = subscription_id(&value["params"]["subscription"]) {
+            self.on_notification(subscription, retstr)
+        } else {
+            warn!("message is neither a response nor a notification: {}", retstr);
+            Ok(())
+        }
+    }
+
+    fn on_close(&mut self, code: CloseCode, reason: &str) {
+        warn!("websocket connection closed ({:?}): {}", code, reason);
+        *self.connection.lock().unwrap() = None;
     }
 }
 
-pub fn on_get_request_msg(msg: Message, out: Sender, result: ThreadOut<String>) -> Result<()> {
-    info!("Got get_request_msg {}", msg);
-    let retstr = msg.as_text().unwrap();
-    let value: serde_json::Value = serde_json::from_str(retstr).unwrap();
+impl RpcClient {
+    /// Sends the subscription requests kept by `on_connection_lost` again.
+    fn resume_subscriptions(&mut self) -> Result<()> {
+        let mut requests = self.requests.lock().unwrap();
+        let resumed: Vec<PendingRequest> = requests.drain().map(|(_, request)| request).collect();
+        for request in resumed {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst).to_string();
+            let mut jsonreq = request.jsonreq.clone();
+            jsonreq["id"] = Value::String(id.clone());
+            info!("resuming subscription: {}", jsonreq);
+            self.out.send(jsonreq.to_string())?;
+            requests.insert(id, request);
+        }
+        Ok(())
+    }
+
+    fn on_response(&mut self, id: String, msg: &str, value: &Value) -> Result<()> {
+        let request = match self.requests.lock().unwrap().remove(&id) {
+            Some(request) => request,
+            None => {
+                debug!("no pending request with id {}", id);
+                return Ok(());
+            }
+        };
+
+        let finished = (request.on_message_fn)(msg, &request.result);
+        if let Some(subscription) = subscription_id(&value["result"]) {
+            if json_req::unsubscribe_method(&request.method).is_some() {
+                if finished {
+                    unsubscribe(&self.out, &self.next_id, &request.method, &value["result"])?;
+                } else {
+                    self.subscriptions
+                        .lock()
+                        .unwrap()
+                        .insert(subscription, request);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn on_notification(&mut self, subscription: String, msg: &str) -> Result<()> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let finished = match subscriptions.get(&subscription) {
+            Some(sub) => (sub.on_message_fn)(msg, &sub.result),
+            None => {
+                debug!("no active subscription with id {}", subscription);
+                return Ok(());
+            }
+        };
+        if finished {
+            let sub = subscriptions.remove(&subscription).unwrap();
+            drop(subscriptions);
+            let value: Value = serde_json::from_str(msg).unwrap_or_default();
+            unsubscribe(
+                &self.out,
+                &self.next_id,
+                &sub.method,
+                &value["params"]["subscription"],
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Drops all requests and subscriptions of a lost connection, which wakes up everyone waiting
+/// for them. Subscriptions that can be resumed are kept as requests to be sent again.
+pub fn on_connection_lost(requests: &PendingRequests, subscriptions: &Subscriptions) {
+    let mut requests = requests.lock().unwrap();
+    let lost: Vec<PendingRequest> = requests
+        .drain()
+        .map(|(_, request)| request)
+        .chain(subscriptions.lock().unwrap().drain().map(|(_, sub)| sub))
+        .collect();
+    for request in lost {
+        if json_req::is_resumable(&request.method) {
+            requests.insert(request.id.clone(), request);
+        }
+    }
+}
 
-    result.send(value["result"].to_string()).unwrap();
-    out.close(CloseCode::Normal).unwrap();
+/// Cancels the subscription with id `subscription`, which was started with `method`.
+pub fn unsubscribe(
+    out: &Sender,
+    next_id: &AtomicU32,
+    method: &str,
+    subscription: &Value,
+) -> Result<()> {
+    if let Some(unsubscribe_method) = json_req::unsubscribe_method(method) {
+        let id = next_id.fetch_add(1, Ordering::SeqCst);
+        let jsonreq = json_req::unsubscribe_with_id(unsubscribe_method, subscription, id);
+        debug!("unsubscribing: {}", jsonreq);
+        out.send(jsonreq.to_string())?;
+    }
     Ok(())
 }
 
-pub fn on_subscription_msg(msg: Message, _out: Sender, result: ThreadOut<String>) -> Result<()> {
+/// Request ids are sent as strings, but accept numbers as well.
+pub(crate) fn request_id(id: &Value) -> Option<String> {
+    match id {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Subscription ids are numbers or strings, depending on the node version.
+fn subscription_id(id: &Value) -> Option<String> {
+    match id {
+        Value::String(_) | Value::Number(_) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Forwards the whole response, which is interpreted by `parse_response` on the receiving end.
+pub fn on_get_request_msg(msg: &str, result: &ResultSender) -> bool {
+    info!("Got get_request_msg {}", msg);
+    let _ = result.send(msg.to_string());
+    true
+}
+
+pub fn on_subscription_msg(msg: &str, result: &ResultSender) -> bool {
     info!("got on_subscription_msg {}", msg);
-    let retstr = msg.as_text().unwrap();
-    let value: serde_json::Value = serde_json::from_str(retstr).unwrap();
+    let value: Value = match serde_json::from_str(msg) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("could not parse subscription message: {:?}", e);
+            return false;
+        }
+    };
     match value["id"].as_str() {
-        Some(_idstr) => {}
+        Some(_idstr) => false,
         _ => {
             // subscriptions
             debug!("no id field found in response. must be subscription");
@@ -76,109 +359,341 @@ pub fn on_subscription_msg(msg: Message, _out: Sender, result: ThreadOut<String>
                     let changes = &value["params"]["result"]["changes"];
 
                     match changes[0][1].as_str() {
-                        Some(change_set) => result.send(change_set.to_owned()).unwrap(),
-                        None => println!("No events happened"),
-                    };
+                        // the receiver hung up, so we can unsubscribe
+                        Some(change_set) => result.send(change_set.to_owned()).is_err(),
+                        None => {
+                            println!("No events happened");
+                            false
+                        }
+                    }
+                }
+                _ => {
+                    error!("unsupported method");
+                    false
                 }
-                _ => error!("unsupported method"),
             }
         }
+    }
+}
+
+/// Forwards the `result` of every notification of a subscription, e.g. new heads. Ends the
+/// subscription if the node rejected it or the receiving end has hung up.
+pub fn on_notification_msg(msg: &str, result: &ResultSender) -> bool {
+    debug!("got notification msg {}", msg);
+    let value: Value = match serde_json::from_str(msg) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("could not parse notification: {:?}", e);
+            return false;
+        }
     };
-    Ok(())
+    if let Some(obj) = value["error"].as_object() {
+        error!("subscription failed: {:?}", obj);
+        return true;
+    }
+    match value["params"]["result"] {
+        Value::Null => false,
+        ref res => result.send(res.to_string()).is_err(),
+    }
 }
 
-pub fn on_extrinsic_msg_until_finalized(msg: Message, out: Sender, result: ThreadOut<String>) -> Result<()> {
-    let retstr = msg.as_text().unwrap();
-    debug!("got msg {}", retstr);
-    match parse_status(retstr) {
-        (XtStatus::Finalized, val) => end_process(out, result, val),
-        (XtStatus::Error, _) => end_process(out, result, None),
-        (XtStatus::Future, _) => { 
+pub fn on_extrinsic_msg_until_finalized(msg: &str, result: &ResultSender) -> bool {
+    debug!("got msg {}", msg);
+    match parse_status(msg) {
+        (XtStatus::Finalized, _) => end_process(result, msg),
+        (XtStatus::Error, _) => end_process(result, msg),
+        (XtStatus::Future, _) => {
             warn!("extrinsic has 'future' status. aborting");
-            end_process(out, result, None);
-        },
-        _ => ()
+            end_process(result, msg)
+        }
+        _ => false,
+    }
+}
+
+pub fn on_extrinsic_msg_until_in_block(msg: &str, result: &ResultSender) -> bool {
+    debug!("got msg {}", msg);
+    match parse_status(msg) {
+        (XtStatus::Finalized, _) => end_process(result, msg),
+        (XtStatus::InBlock, _) => end_process(result, msg),
+        (XtStatus::Future, _) => end_process(result, msg),
+        (XtStatus::Error, _) => end_process(result, msg),
+        _ => false,
+    }
+}
+
+pub fn on_extrinsic_msg_until_ready(msg: &str, result: &ResultSender) -> bool {
+    debug!("got msg {}", msg);
+    match parse_status(msg) {
+        (XtStatus::Finalized, _) => end_process(result, msg),
+        (XtStatus::InBlock, _) => end_process(result, msg),
+        (XtStatus::Broadcast, _) => end_process(result, msg),
+        (XtStatus::Ready, _) => end_process(result, msg),
+        (XtStatus::Future, _) => end_process(result, msg),
+        (XtStatus::Error, _) => end_process(result, msg),
+        _ => false,
+    }
+}
+
+/// Forwards every status update of a watched extrinsic, or the error the node rejected it with.
+/// Ends after the final status. The messages are interpreted by `parse_transaction_status`.
+pub fn on_transaction_status_msg(msg: &str, result: &ResultSender) -> bool {
+    debug!("got msg {}", msg);
+    let value: Value = match serde_json::from_str(msg) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("could not parse extrinsic status: {:?}", e);
+            return false;
+        }
     };
-    Ok(())
+    if json_rpc_error(&value).is_some() {
+        return end_process(result, msg);
+    }
+    if value.get("id").is_some() {
+        // response with the subscription id
+        return false;
+    }
+    let finished = serde_json::from_value::<TransactionStatus>(value["params"]["result"].clone())
+        .map(|status| status.is_final())
+        .unwrap_or(false);
+    result.send(msg.to_string()).is_err() || finished
+}
+
+/// Returns the final message to the calling thread, which interprets the status itself.
+fn end_process(result: &ResultSender, msg: &str) -> bool {
+    let _ = result.send(msg.to_string());
+    true
+}
+
+/// Error code of the response a transport delivers in place of one that did not arrive in time,
+/// outside the codes of the node. See `timeout_response`.
+const TIMEOUT_ERROR_CODE: i64 = -32099;
+
+/// The response delivered to the receiver of a request the node did not answer within
+/// `timeout`. `parse_response` and `parse_transaction_status` turn it into `RpcError::Timeout`.
+pub fn timeout_response(timeout: Duration) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "error": {
+            "code": TIMEOUT_ERROR_CODE,
+            "message": "No response from node in time",
+            "data": { "timeoutMillis": timeout.as_millis() as u64 },
+        },
+        "id": null,
+    })
+    .to_string()
+}
+
+/// Extracts the `result` of a response as json string, or the error the node responded with.
+pub fn parse_response(msg: &str) -> RpcResult<String> {
+    let value: Value = serde_json::from_str(msg)?;
+    match response_error(&value) {
+        Some(error) => Err(error),
+        None => Ok(value["result"].to_string()),
+    }
 }
 
-pub fn on_extrinsic_msg_until_ready(msg: Message, out: Sender, result: ThreadOut<String>) -> Result<()> {
-    let retstr = msg.as_text().unwrap();
-    debug!("got msg {}", retstr);
-    match parse_status(retstr) {
-        (XtStatus::Finalized, val) => end_process(out, result, val),
-        (XtStatus::Ready, _) => end_process(out, result, None),
-        (XtStatus::Future, _) => end_process(out, result, None),
-        (XtStatus::Error, _) => end_process(out, result, None),
-        _ => ()
+/// The error the node responded with, or the timeout of a `timeout_response`.
+fn response_error(value: &Value) -> Option<RpcError> {
+    let error = json_rpc_error(value)?;
+    let timeout = match &error.data {
+        Some(data) if error.code == TIMEOUT_ERROR_CODE => data["timeoutMillis"].as_u64(),
+        _ => None,
     };
-    Ok(())
+    match timeout {
+        Some(millis) => Some(RpcError::Timeout(Duration::from_millis(millis))),
+        None => Some(RpcError::JsonRpc(error)),
+    }
 }
 
-fn end_process(out: Sender, result: ThreadOut<String>, value: Option<String>) {
-    // return result to calling thread
-    let val = value.unwrap_or("nix".to_string());
-    result.send(val).unwrap();
-    out.close(CloseCode::Normal).unwrap();
+fn json_rpc_error(value: &Value) -> Option<JsonRpcError> {
+    value
+        .get("error")
+        .and_then(|error| serde_json::from_value(error.clone()).ok())
 }
 
-fn parse_status(msg: &str) -> (XtStatus, Option<String>) {
-    let value: serde_json::Value = serde_json::from_str(msg).unwrap();
-    match value["error"].as_object() {
-        Some(obj) => {
-            error!("extrinsic error code {}: {}", 
-                        obj.get("code").unwrap().as_u64().unwrap(), 
-                        obj.get("message").unwrap().as_str().unwrap());
+pub fn parse_status(msg: &str) -> (XtStatus, Option<String>) {
+    let value: Value = match serde_json::from_str(msg) {
+        Ok(value) => value,
+        Err(e) => {
+            error!("could not parse extrinsic status: {:?}", e);
+            return (XtStatus::Error, None);
+        }
+    };
+    if let Some(error) = json_rpc_error(&value) {
+        error!("extrinsic error code {}: {}", error.code, error.message);
+        return (XtStatus::Error, None);
+    }
+    let status = match serde_json::from_value(value["params"]["result"].clone()) {
+        Ok(status) => status,
+        Err(_) => return (XtStatus::Unknown, None),
+    };
+    match status {
+        TransactionStatus::Future => (XtStatus::Future, None),
+        TransactionStatus::Ready => (XtStatus::Ready, None),
+        TransactionStatus::Broadcast(_) => (XtStatus::Broadcast, None),
+        TransactionStatus::InBlock(hash) => {
+            info!("in block: {:?}", hash);
+            (XtStatus::InBlock, Some(json_hash(&hash)))
+        }
+        TransactionStatus::Finalized(hash) => {
+            info!("finalized: {:?}", hash);
+            (XtStatus::Finalized, Some(json_hash(&hash)))
+        }
+        TransactionStatus::Retracted(_) => (XtStatus::Unknown, None),
+        status => {
+            warn!("extrinsic will not be included: {:?}", status);
             (XtStatus::Error, None)
-        },
-        None => {
-            match value["params"]["result"].as_object() {
-                Some(obj) => {
-                    if let Some(hash) = obj.get("finalized") {
-                        info!("finalized: {:?}", hash);
-                        (XtStatus::Finalized, Some( hash.to_string()))
-                    } else {
-                        (XtStatus::Unknown, None)
-                    }
-                },
-                None => {
-                    match value["params"]["result"].as_str() {
-                        Some("ready") => (XtStatus::Ready, None),
-                        Some("future") => (XtStatus::Future, None),
-                        Some(&_) => (XtStatus::Unknown, None),
-                        None => (XtStatus::Unknown, None),
-                    }
-                }
-            }
         }
     }
 }
 
+/// Returns the status update, or the error the node rejected the extrinsic with.
+pub fn parse_transaction_status(msg: &str) -> RpcResult<TransactionStatus> {
+    let value: Value = serde_json::from_str(msg)?;
+    match response_error(&value) {
+        Some(error) => Err(error),
+        None => Ok(serde_json::from_value(value["params"]["result"].clone())?),
+    }
+}
+
+/// The hash as json string, as the node sends it.
+fn json_hash(hash: &Hash) -> String {
+    serde_json::to_string(hash).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn timeout_response_is_parsed_as_timeout() {
+        let timeout = Duration::from_millis(1500);
+        let msg = timeout_response(timeout);
+
+        assert!(matches!(parse_response(&msg), Err(RpcError::Timeout(t)) if t == timeout));
+        assert!(matches!(parse_transaction_status(&msg), Err(RpcError::Timeout(t)) if t == timeout));
+        let msg = r#"{"jsonrpc":"2.0","error":{"code":-32099,"message":"Busy"},"id":"1"}"#;
+        assert!(matches!(parse_response(msg), Err(RpcError::JsonRpc(_))));
+    }
+
     #[test]
     fn extrinsic_status_parsed_correctly(){
         let msg = "{\"jsonrpc\":\"2.0\",\"result\":7185,\"id\":\"3\"}";
-        assert_eq!(parse_status(msg), None);
+        assert_eq!(parse_status(msg).0, XtStatus::Unknown);
 
         let msg = "{\"jsonrpc\":\"2.0\",\"method\":\"author_extrinsicUpdate\",\"params\":{\"result\":\"ready\",\"subscription\":7185}}";
-        assert_eq!(parse_status(msg), Some(XtStatus::Ready));
+        assert_eq!(parse_status(msg).0, XtStatus::Ready);
 
         let msg = "{\"jsonrpc\":\"2.0\",\"method\":\"author_extrinsicUpdate\",\"params\":{\"result\":{\"finalized\":\"0x934385b11c483498e2b5bca64c2e8ef76ad6c74d3372a05595d3a50caf758d52\"},\"subscription\":7185}}";
-        assert_eq!(parse_status(msg), Some(XtStatus::Finalized));
+        assert_eq!(
+            parse_status(msg),
+            (
+                XtStatus::Finalized,
+                Some("\"0x934385b11c483498e2b5bca64c2e8ef76ad6c74d3372a05595d3a50caf758d52\"".to_string())
+            )
+        );
 
         let msg = "{\"jsonrpc\":\"2.0\",\"method\":\"author_extrinsicUpdate\",\"params\":{\"result\":\"future\",\"subscription\":2}}";
-        assert_eq!(parse_status(msg), Some(XtStatus::Future));
+        assert_eq!(parse_status(msg).0, XtStatus::Future);
 
         let msg = "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":-32700,\"message\":\"Parse error\"},\"id\":null}";
-        assert_eq!(parse_status(msg), Some(XtStatus::Error));
+        assert_eq!(parse_status(msg).0, XtStatus::Error);
 
         let msg = "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":1010,\"message\":\"Invalid Transaction\",\"data\":0},\"id\":\"4\"}";
-        assert_eq!(parse_status(msg), Some(XtStatus::Error));
+        assert_eq!(parse_status(msg).0, XtStatus::Error);
         
         let msg = "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":1001,\"message\":\"Extrinsic has invalid format.\"},\"id\":\"0\"}";
-        assert_eq!(parse_status(msg), Some(XtStatus::Error));
+        assert_eq!(parse_status(msg).0, XtStatus::Error);
+    }
+
+    #[test]
+    fn transaction_status_parsed_correctly() {
+        let update = |result: &str| {
+            format!("{{\"jsonrpc\":\"2.0\",\"method\":\"author_extrinsicUpdate\",\"params\":{{\"result\":{},\"subscription\":7185}}}}", result)
+        };
+        let hash = Hash::repeat_byte(0x93);
+        let hash_str = "\"0x9393939393939393939393939393939393939393939393939393939393939393\"";
+
+        let cases = vec![
+            ("\"future\"".to_string(), TransactionStatus::Future),
+            ("\"ready\"".to_string(), TransactionStatus::Ready),
+            ("{\"broadcast\":[\"QmPeer\"]}".to_string(), TransactionStatus::Broadcast(vec!["QmPeer".into()])),
+            (format!("{{\"inBlock\":{}}}", hash_str), TransactionStatus::InBlock(hash)),
+            (format!("{{\"retracted\":{}}}", hash_str), TransactionStatus::Retracted(hash)),
+            (format!("{{\"finalized\":{}}}", hash_str), TransactionStatus::Finalized(hash)),
+            (format!("{{\"usurped\":{}}}", hash_str), TransactionStatus::Usurped(hash)),
+            ("\"dropped\"".to_string(), TransactionStatus::Dropped),
+            ("\"invalid\"".to_string(), TransactionStatus::Invalid),
+        ];
+        for (result, status) in cases {
+            assert_eq!(parse_transaction_status(&update(&result)).unwrap(), status);
+        }
+
+        assert_eq!(
+            parse_status(&update(&format!("{{\"inBlock\":{}}}", hash_str))),
+            (XtStatus::InBlock, Some(hash_str.to_string()))
+        );
+        assert_eq!(parse_status(&update("\"dropped\"")).0, XtStatus::Error);
+
+        let msg = "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":1010,\"message\":\"Invalid Transaction\",\"data\":0},\"id\":\"4\"}";
+        match parse_transaction_status(msg) {
+            Err(RpcError::JsonRpc(error)) => assert_eq!(error.code, 1010),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn only_resumable_subscriptions_survive_connection_loss() {
+        let requests: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let subscriptions: Subscriptions = Arc::new(Mutex::new(HashMap::new()));
+        let (result_in, _result_out) = std::sync::mpsc::channel();
+        let pending = |id: &str, jsonreq: Value| PendingRequest {
+            id: id.to_string(),
+            method: jsonreq["method"].as_str().unwrap().to_string(),
+            jsonreq,
+            result: ResultSender::from(result_in.clone()),
+            on_message_fn: on_notification_msg,
+        };
+        requests
+            .lock()
+            .unwrap()
+            .insert("1".into(), pending("1", json_req::state_get_metadata()));
+        requests
+            .lock()
+            .unwrap()
+            .insert("2".into(), pending("2", json_req::chain_subscribe_new_heads()));
+        subscriptions.lock().unwrap().insert(
+            "7".into(),
+            pending("3", json_req::author_submit_and_watch_extrinsic("0x00")),
+        );
+        subscriptions
+            .lock()
+            .unwrap()
+            .insert("8".into(), pending("4", json_req::state_subscribe_storage("0x26aa")));
+
+        on_connection_lost(&requests, &subscriptions);
+
+        let mut resumed: Vec<String> = requests.lock().unwrap().keys().cloned().collect();
+        resumed.sort();
+        assert_eq!(resumed, vec!["2", "4"]);
+        assert!(subscriptions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn response_parsed_correctly() {
+        let msg = "{\"jsonrpc\":\"2.0\",\"result\":\"0x01\",\"id\":\"1\"}";
+        assert_eq!(parse_response(msg).unwrap(), "\"0x01\"");
+
+        let msg = "{\"jsonrpc\":\"2.0\",\"error\":{\"code\":1010,\"message\":\"Invalid Transaction\",\"data\":0},\"id\":\"4\"}";
+        match parse_response(msg) {
+            Err(RpcError::JsonRpc(error)) => {
+                assert_eq!(error.code, 1010);
+                assert_eq!(error.message, "Invalid Transaction");
+                assert_eq!(error.data, Some(Value::from(0)));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        assert!(parse_response("not json").is_err());
     }
 }
@@ -0,0 +1,419 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde_json::Value;
+
+use super::client::{OnMessageFn, ResultSender};
+use super::json_req;
+use super::ws_client::WsRpcClient;
+use super::{RpcError, RpcResult, RpcTransport, DEFAULT_REQUEST_TIMEOUT};
+
+/// Time between two health checks of the endpoints, unless configured otherwise.
+pub const DEFAULT_HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Routes requests to one of several nodes of the same chain.
+///
+/// All requests go to the same endpoint as long as it works. On a transport error the endpoint
+/// is marked unhealthy and the request is retried on the next healthy one. A background thread
+/// checks `system_health` of every endpoint periodically and marks it healthy again once it is
+/// synced. An endpoint whose genesis hash differs from the one of the first endpoint is never
+/// used, so all requests go to the same chain.
+pub struct FailoverRpcClient<T = WsRpcClient> {
+    shared: Arc<Endpoints<T>>,
+}
+
+struct Endpoints<T> {
+    endpoints: Vec<Endpoint<T>>,
+    /// Index of the endpoint that answered the last request.
+    active: AtomicUsize,
+    /// Genesis hash all endpoints must have, as json string.
+    genesis_hash: String,
+}
+
+struct Endpoint<T> {
+    client: T,
+    healthy: AtomicBool,
+}
+
+impl FailoverRpcClient<WsRpcClient> {
+    /// Connects to all nodes in `urls`. Nodes that are not reachable yet are unhealthy, the
+    /// client keeps trying to connect to them and uses them once they pass the health check.
+    pub fn new(urls: &[&str]) -> RpcResult<Self> {
+        let clients = urls
+            .iter()
+            .map(|url| match WsRpcClient::new(url) {
+                Ok(client) => Ok(client),
+                Err(e) => {
+                    warn!("endpoint {} is not reachable yet: {}", url, e);
+                    WsRpcClient::connect_in_background(url)
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Self::with_transports(clients, DEFAULT_HEALTH_CHECK_INTERVAL)
+    }
+}
+
+impl<T> FailoverRpcClient<T>
+where
+    T: RpcTransport + Send + Sync + 'static,
+{
+    /// Fails with `RpcError::GenesisMismatch` if the endpoints are not all on the same chain.
+    pub fn with_transports(clients: Vec<T>, health_check_interval: Duration) -> RpcResult<Self> {
+        let mut genesis_hash = None;
+        let mut endpoints = Vec::with_capacity(clients.len());
+        for (index, client) in clients.into_iter().enumerate() {
+            let healthy = match client.get_request(
+                json_req::chain_get_genesis_hash(),
+                DEFAULT_REQUEST_TIMEOUT,
+            ) {
+                Ok(hash) => match &genesis_hash {
+                    Some(expected) if expected != &hash => {
+                        return Err(RpcError::GenesisMismatch {
+                            endpoint: index,
+                            expected: expected.clone(),
+                            found: hash,
+                        })
+                    }
+                    Some(_) => true,
+                    None => {
+                        genesis_hash = Some(hash);
+                        true
+                    }
+                },
+                Err(e) => {
+                    warn!("endpoint {} is unavailable: {}", index, e);
+                    false
+                }
+            };
+            endpoints.push(Endpoint {
+                client,
+                healthy: AtomicBool::new(healthy),
+            });
+        }
+
+        let shared = Arc::new(Endpoints {
+            endpoints,
+            active: AtomicUsize::new(0),
+            genesis_hash: genesis_hash.ok_or(RpcError::NoHealthyEndpoint)?,
+        });
+        let weak = Arc::downgrade(&shared);
+        thread::Builder::new()
+            .name("health-check".to_owned())
+            .spawn(move || loop {
+                thread::sleep(health_check_interval);
+                // stop once the client has been dropped
+                match weak.upgrade() {
+                    Some(shared) => shared.check_health(),
+                    None => break,
+                }
+            })?;
+        Ok(Self { shared })
+    }
+}
+
+impl<T: RpcTransport> FailoverRpcClient<T> {
+    /// Whether the endpoint at `index` passed its last health check.
+    pub fn is_healthy(&self, index: usize) -> bool {
+        self.shared.endpoints[index].healthy.load(Ordering::SeqCst)
+    }
+}
+
+impl<T: RpcTransport> Endpoints<T> {
+    fn check_health(&self) {
+        for (index, endpoint) in self.endpoints.iter().enumerate() {
+            let healthy = self.is_healthy(&endpoint.client);
+            if healthy != endpoint.healthy.swap(healthy, Ordering::SeqCst) {
+                info!("endpoint {} is now {}", index, if healthy { "healthy" } else { "unhealthy" });
+            }
+        }
+    }
+
+    fn is_healthy(&self, client: &T) -> bool {
+        let jsonreqs = vec![json_req::system_health(), json_req::chain_get_genesis_hash()];
+        let results = match client.batch_request(jsonreqs, DEFAULT_REQUEST_TIMEOUT) {
+            Ok(results) => results,
+            Err(e) => {
+                debug!("health check failed: {}", e);
+                return false;
+            }
+        };
+        match (&results[0], &results[1]) {
+            (Ok(health), Ok(genesis_hash)) if genesis_hash == &self.genesis_hash => {
+                let health: Value = serde_json::from_str(health).unwrap_or_default();
+                let syncing = health["isSyncing"].as_bool().unwrap_or(true);
+                let has_peers = health["peers"].as_u64().unwrap_or(0) > 0
+                    || !health["shouldHavePeers"].as_bool().unwrap_or(true);
+                !syncing && has_peers
+            }
+            (_, Ok(genesis_hash)) if genesis_hash != &self.genesis_hash => {
+                warn!("endpoint is on another chain with genesis hash {}", genesis_hash);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// Calls `f` with the active endpoint, or the next healthy one as long as `f` fails with a
+    /// transport error. Timeouts are only retried if `retry_timeouts` is set. Returns the index of
+    /// the endpoint that answered with the result.
+    fn route<R>(
+        &self,
+        retry_timeouts: bool,
+        f: impl Fn(&T) -> RpcResult<R>,
+    ) -> RpcResult<(usize, R)> {
+        let count = self.endpoints.len();
+        let start = self.active.load(Ordering::SeqCst);
+        let mut last_error = None;
+        for index in (start..start + count).map(|i| i % count) {
+            let endpoint = &self.endpoints[index];
+            if !endpoint.healthy.load(Ordering::SeqCst) {
+                continue;
+            }
+            match f(&endpoint.client) {
+                Err(RpcError::Timeout(timeout)) if !retry_timeouts => {
+                    return Err(RpcError::Timeout(timeout))
+                }
+                Err(e) if is_transport_error(&e) => {
+                    warn!("endpoint {} failed, failing over: {}", index, e);
+                    endpoint.healthy.store(false, Ordering::SeqCst);
+                    last_error = Some(e);
+                }
+                res => {
+                    self.active.store(index, Ordering::SeqCst);
+                    return res.map(|r| (index, r));
+                }
+            }
+        }
+        Err(last_error.unwrap_or(RpcError::NoHealthyEndpoint))
+    }
+}
+
+/// Errors after which the request is retried on another endpoint.
+fn is_transport_error(error: &RpcError) -> bool {
+    match error {
+        RpcError::Ws(_) | RpcError::Http(_) | RpcError::ConnectionLost | RpcError::Timeout(_) => {
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Whether a request may be sent to another endpoint after it timed out. Extrinsics are not
+/// submitted again, as the first endpoint may still have received them.
+fn is_retryable(jsonreq: &Value) -> bool {
+    !jsonreq["method"]
+        .as_str()
+        .map_or(false, |method| method.starts_with("author_"))
+}
+
+impl<T> Clone for FailoverRpcClient<T> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T: RpcTransport> RpcTransport for FailoverRpcClient<T> {
+    fn get_request(&self, jsonreq: Value, timeout: Duration) -> RpcResult<String> {
+        let (_, result) = self.shared.route(is_retryable(&jsonreq), |client| {
+            client.get_request(jsonreq.clone(), timeout)
+        })?;
+        Ok(result)
+    }
+
+    fn batch_request(
+        &self,
+        jsonreqs: Vec<Value>,
+        timeout: Duration,
+    ) -> RpcResult<Vec<RpcResult<String>>> {
+        let retry_timeouts = jsonreqs.iter().all(is_retryable);
+        let (_, results) = self.shared.route(retry_timeouts, |client| {
+            client.batch_request(jsonreqs.clone(), timeout)
+        })?;
+        Ok(results)
+    }
+
+    /// The subscription stays with the endpoint it was started on.
+    fn subscribe(
+        &self,
+        jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String> {
+        let (index, id) = self.shared.route(is_retryable(&jsonreq), |client| {
+            client.subscribe(jsonreq.clone(), result_in.clone(), on_message_fn)
+        })?;
+        // ids are only unique per endpoint
+        Ok(format!("{}:{}", index, id))
+    }
+
+    fn cancel(&self, id: &str) {
+        let mut parts = id.splitn(2, ':');
+        let index = parts.next().and_then(|index| index.parse::<usize>().ok());
+        if let (Some(index), Some(id)) = (index, parts.next()) {
+            if let Some(endpoint) = self.shared.endpoints.get(index) {
+                endpoint.client.cancel(id);
+            }
+        }
+    }
+
+    fn subscribe_reconnects(&self, sender: ResultSender) {
+        for endpoint in &self.shared.endpoints {
+            endpoint.client.subscribe_reconnects(sender.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::MockRpcClient;
+    use crate::Hash;
+    use serde_json::json;
+
+    fn node(genesis_hash: &str) -> MockRpcClient {
+        MockRpcClient::new().with_response("chain_getBlockHash", json!(genesis_hash))
+    }
+
+    #[test]
+    fn endpoints_of_another_chain_are_rejected() {
+        let clients = vec![node("0x01"), node("0x01"), node("0x02")];
+
+        match FailoverRpcClient::with_transports(clients, DEFAULT_HEALTH_CHECK_INTERVAL) {
+            Err(RpcError::GenesisMismatch {
+                endpoint, found, ..
+            }) => {
+                assert_eq!(endpoint, 2);
+                assert_eq!(found, "\"0x02\"");
+            }
+            _ => panic!("expected a genesis mismatch"),
+        }
+    }
+
+    #[test]
+    fn unavailable_endpoints_are_skipped() {
+        let clients = vec![MockRpcClient::new(), node("0x01")];
+
+        let client =
+            FailoverRpcClient::with_transports(clients, DEFAULT_HEALTH_CHECK_INTERVAL).unwrap();
+
+        assert!(!client.is_healthy(0));
+        assert!(client.is_healthy(1));
+        let hash = client
+            .get_request(json_req::chain_get_genesis_hash(), DEFAULT_REQUEST_TIMEOUT)
+            .unwrap();
+        assert_eq!(hash, "\"0x01\"");
+    }
+
+    /// Answers the genesis hash, but lets every other request time out if `times_out` is set.
+    #[derive(Clone)]
+    struct Flaky {
+        client: MockRpcClient,
+        times_out: bool,
+    }
+
+    impl RpcTransport for Flaky {
+        fn get_request(&self, jsonreq: Value, timeout: Duration) -> RpcResult<String> {
+            if self.times_out && jsonreq["method"] != "chain_getBlockHash" {
+                return Err(RpcError::Timeout(timeout));
+            }
+            self.client.get_request(jsonreq, timeout)
+        }
+
+        fn subscribe(
+            &self,
+            jsonreq: Value,
+            result_in: ResultSender,
+            on_message_fn: OnMessageFn,
+        ) -> RpcResult<String> {
+            self.client.subscribe(jsonreq, result_in, on_message_fn)
+        }
+
+        fn cancel(&self, _id: &str) {}
+    }
+
+    #[test]
+    fn timed_out_extrinsics_are_not_submitted_again() {
+        let slow = Flaky {
+            client: node("0x01"),
+            times_out: true,
+        };
+        let fast = Flaky {
+            client: node("0x01")
+                .with_response("author_submitExtrinsic", json!("0xab"))
+                .with_response("chain_getHeader", json!(null)),
+            times_out: false,
+        };
+        let client = FailoverRpcClient::with_transports(
+            vec![slow, fast.clone()],
+            DEFAULT_HEALTH_CHECK_INTERVAL,
+        )
+        .unwrap();
+
+        match client.get_request(
+            json_req::author_submit_extrinsic("0x00"),
+            DEFAULT_REQUEST_TIMEOUT,
+        ) {
+            Err(RpcError::Timeout(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(fast.client.requests().len(), 1);
+        assert!(client.is_healthy(0));
+
+        let header = client
+            .get_request(json_req::chain_get_header::<Hash>(None), DEFAULT_REQUEST_TIMEOUT)
+            .unwrap();
+        assert_eq!(header, "null");
+        assert!(!client.is_healthy(0));
+    }
+
+    #[cfg(feature = "test-support")]
+    #[test]
+    fn endpoints_unreachable_at_startup_are_used_once_they_are_up() {
+        use crate::test_support::FakeNode;
+        use std::net::TcpListener;
+        use std::time::Instant;
+
+        let node = FakeNode::new(node_runtime::Runtime::metadata())
+            .spawn()
+            .unwrap();
+        let down = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let client = FailoverRpcClient::new(&[&format!("ws://{}", down), &node.url()]).unwrap();
+        assert!(!client.is_healthy(0));
+        assert!(client.is_healthy(1));
+
+        let _late = FakeNode::new(node_runtime::Runtime::metadata())
+            .spawn_at(&down.to_string())
+            .unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !client.is_healthy(0) {
+            assert!(Instant::now() < deadline, "endpoint did not become healthy");
+            thread::sleep(Duration::from_millis(100));
+            client.shared.check_health();
+        }
+    }
+}
@@ -0,0 +1,299 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, error};
+use serde_json::Value;
+
+use super::client::{parse_response, request_id, timeout_response, OnMessageFn, ResultSender};
+use super::json_req;
+use super::{RpcError, RpcResult, RpcTransport, DEFAULT_REQUEST_TIMEOUT};
+
+/// Sends every request as HTTP POST to the node's RPC port (9933 by default).
+///
+/// HTTP has no way to push notifications, so subscriptions are rejected with
+/// `RpcError::SubscriptionNotSupported`. Use `Api::submit_extrinsic` instead of
+/// `Api::send_extrinsic` to send extrinsics through this transport.
+///
+/// The single requests sent through `subscribe`, e.g. by `AsyncApi`, are posted on a separate
+/// thread, so `subscribe` returns right away.
+#[derive(Clone)]
+pub struct HttpRpcClient {
+    url: String,
+    next_id: Arc<AtomicU32>,
+    timeout: Duration,
+}
+
+impl HttpRpcClient {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            next_id: Arc::new(AtomicU32::new(1)),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Timeout of the requests sent through `subscribe`, which has no timeout argument. The
+    /// other requests use the timeout they are sent with.
+    pub fn set_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Posts the request with a new id and returns the id together with the response.
+    fn post(&self, mut jsonreq: Value, timeout: Duration) -> RpcResult<(String, String)> {
+        let id = self.next_id();
+        jsonreq["id"] = Value::String(id.clone());
+        let msg = self.post_body(&jsonreq, timeout)?;
+        Ok((id, msg))
+    }
+
+    fn next_id(&self) -> String {
+        self.next_id.fetch_add(1, Ordering::SeqCst).to_string()
+    }
+
+    fn post_body(&self, body: &Value, timeout: Duration) -> RpcResult<String> {
+        debug!("sending request: {}", body);
+        let response = ureq::post(&self.url)
+            .set("Content-Type", "application/json")
+            .timeout(timeout)
+            .send_string(&body.to_string());
+        match response.synthetic_error() {
+            Some(ureq::Error::Io(e))
+                if e.kind() == io::ErrorKind::TimedOut || e.kind() == io::ErrorKind::WouldBlock =>
+            {
+                return Err(RpcError::Timeout(timeout));
+            }
+            Some(err) => return Err(RpcError::Http(err.to_string())),
+            None => {}
+        }
+        if !response.ok() {
+            return Err(RpcError::Http(format!(
+                "node responded with status {} {}",
+                response.status(),
+                response.status_text()
+            )));
+        }
+        response
+            .into_string()
+            .map_err(|e| RpcError::Http(e.to_string()))
+    }
+}
+
+impl RpcTransport for HttpRpcClient {
+    fn get_request(&self, jsonreq: Value, timeout: Duration) -> RpcResult<String> {
+        let (_, msg) = self.post(jsonreq, timeout)?;
+        debug!("got response {}", msg);
+        parse_response(&msg)
+    }
+
+    fn batch_request(
+        &self,
+        jsonreqs: Vec<Value>,
+        timeout: Duration,
+    ) -> RpcResult<Vec<RpcResult<String>>> {
+        if jsonreqs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut ids = Vec::with_capacity(jsonreqs.len());
+        let batch: Vec<Value> = jsonreqs
+            .into_iter()
+            .map(|mut jsonreq| {
+                let id = self.next_id();
+                jsonreq["id"] = Value::String(id.clone());
+                ids.push(id);
+                jsonreq
+            })
+            .collect();
+        let msg = self.post_body(&Value::Array(batch), timeout)?;
+        debug!("got response {}", msg);
+
+        // the node may answer in any order
+        let mut responses: HashMap<String, Value> = serde_json::from_str::<Vec<Value>>(&msg)?
+            .into_iter()
+            .filter_map(|response| {
+                let id = request_id(&response["id"])?;
+                Some((id, response))
+            })
+            .collect();
+        Ok(ids
+            .into_iter()
+            .map(|id| match responses.remove(&id) {
+                Some(response) => parse_response(&response.to_string()),
+                None => Err(RpcError::MissingBatchResponse(id)),
+            })
+            .collect())
+    }
+
+    fn subscribe(
+        &self,
+        mut jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String> {
+        let method = jsonreq["method"].as_str().unwrap_or_default().to_string();
+        if json_req::unsubscribe_method(&method).is_some() {
+            return Err(RpcError::SubscriptionNotSupported(method));
+        }
+        let id = self.next_id();
+        jsonreq["id"] = Value::String(id.clone());
+        let client = self.clone();
+        thread::Builder::new()
+            .name("http-request".to_owned())
+            .spawn(move || match client.post_body(&jsonreq, client.timeout) {
+                Ok(msg) => {
+                    on_message_fn(&msg, &result_in);
+                }
+                Err(RpcError::Timeout(timeout)) => {
+                    let _ = result_in.send(timeout_response(timeout));
+                }
+                // dropping `result_in` tells the receiver that there will be no response
+                Err(e) => error!("request {} failed: {}", jsonreq, e),
+            })?;
+        Ok(id)
+    }
+
+    /// The response is delivered once it has been received, even after the receiver has been
+    /// dropped, so there is nothing to cancel.
+    fn cancel(&self, _id: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    /// Answers a single HTTP request with what `respond` returns for its json body.
+    fn serve_once<F>(respond: F) -> String
+    where
+        F: FnOnce(Value) -> Value + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut header = line.splitn(2, ':');
+                match (header.next(), header.next()) {
+                    (Some(name), Some(value)) if name.eq_ignore_ascii_case("content-length") => {
+                        content_length = value.trim().parse().unwrap()
+                    }
+                    _ if line.trim().is_empty() => break,
+                    _ => {}
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            let response = respond(serde_json::from_slice(&body).unwrap()).to_string();
+            write!(
+                &stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                response.len(),
+                response
+            )
+            .unwrap();
+        });
+        url
+    }
+
+    #[test]
+    fn batch_responses_are_matched_by_id() {
+        // answers in reverse order, with an error for the second request
+        let url = serve_once(|batch| {
+            let responses = batch
+                .as_array()
+                .unwrap()
+                .iter()
+                .enumerate()
+                .rev()
+                .map(|(i, request)| match i {
+                    1 => json!({
+                        "jsonrpc": "2.0",
+                        "error": { "code": -32601, "message": "Method not found" },
+                        "id": request["id"],
+                    }),
+                    _ => json!({ "jsonrpc": "2.0", "result": request["method"], "id": request["id"] }),
+                })
+                .collect();
+            Value::Array(responses)
+        });
+        let client = HttpRpcClient::new(&url);
+        let unknown = json!({ "jsonrpc": "2.0", "method": "foo_bar", "params": [], "id": "1" });
+
+        let results = client
+            .batch_request(
+                vec![
+                    json_req::chain_get_genesis_hash(),
+                    unknown,
+                    json_req::state_get_runtime_version(),
+                ],
+                DEFAULT_REQUEST_TIMEOUT,
+            )
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), "\"chain_getBlockHash\"");
+        match &results[1] {
+            Err(RpcError::JsonRpc(error)) => assert_eq!(error.code, -32601),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(results[2].as_ref().unwrap(), "\"state_getRuntimeVersion\"");
+    }
+
+    #[test]
+    fn batch_responses_with_numeric_ids_are_matched() {
+        let url = serve_once(|batch| {
+            let responses = batch
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|request| {
+                    let id: u64 = request["id"].as_str().unwrap().parse().unwrap();
+                    json!({ "jsonrpc": "2.0", "result": request["method"], "id": id })
+                })
+                .collect();
+            Value::Array(responses)
+        });
+        let client = HttpRpcClient::new(&url);
+
+        let results = client
+            .batch_request(
+                vec![json_req::chain_get_genesis_hash(), json_req::state_get_runtime_version()],
+                DEFAULT_REQUEST_TIMEOUT,
+            )
+            .unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap(), "\"chain_getBlockHash\"");
+        assert_eq!(results[1].as_ref().unwrap(), "\"state_getRuntimeVersion\"");
+    }
+}
@@ -15,15 +15,15 @@
 
 */
 
+use codec::Encode;
 use serde_json::{json, Value};
-use crate::Hash;
 
 pub const REQUEST_TRANSFER: u32 = 3;
 
-pub fn chain_get_header(hash: Option<Hash>) -> Value {
+pub fn chain_get_header<H: Encode>(hash: Option<H>) -> Value {
     json!({
         "method": "chain_getHeader",
-        "params": [hash],
+        "params": [hex_hash(hash)],
         "jsonrpc": "2.0",
         "id":"1"
     })
@@ -46,10 +46,10 @@ pub fn chain_get_block_hash_with_id(number: Option<u32>, id: u32) -> Value {
     })
 }
 
-pub fn chain_get_block(hash: Option<Hash>) -> Value {
+pub fn chain_get_block<H: Encode>(hash: Option<H>) -> Value {
     json!({
         "method": "chain_getBlock",
-        "params": [hash],
+        "params": [hex_hash(hash)],
         "jsonrpc": "2.0",
         "id":"1"
     })
@@ -64,6 +64,38 @@ pub fn chain_get_finalized_head() -> Value {
     })
 }
 
+pub fn chain_subscribe_new_heads() -> Value {
+    json!({
+        "method": "chain_subscribeNewHeads",
+        "params": null,
+        "jsonrpc": "2.0",
+        "id": "1",
+    })
+}
+
+pub fn chain_subscribe_finalized_heads() -> Value {
+    json!({
+        "method": "chain_subscribeFinalizedHeads",
+        "params": null,
+        "jsonrpc": "2.0",
+        "id": "1",
+    })
+}
+
+pub fn system_health() -> Value {
+    json!({
+        "method": "system_health",
+        "params": null,
+        "jsonrpc": "2.0",
+        "id": "1",
+    })
+}
+
+/// The next nonce of the SS58 `account`, counting its extrinsics in the transaction pool.
+pub fn system_account_next_index(account: &str) -> Value {
+    json_req("system_accountNextIndex", account, 1)
+}
+
 pub fn state_get_metadata() -> Value {
     state_get_metadata_with_id(1)
 }
@@ -111,6 +143,24 @@ pub fn state_get_storage_with_id(key_hash: &str, id: u32) -> Value {
     json_req("state_getStorage", key_hash, id)
 }
 
+/// Reads the storage at `key_hash` in the state of block `at`, or the best block if `None`.
+pub fn state_get_storage_at<H: Encode>(key_hash: &str, at: Option<H>) -> Value {
+    json!({
+        "method": "state_getStorage",
+        "params": [key_hash, hex_hash(at)],
+        "jsonrpc": "2.0",
+        "id": "1",
+    })
+}
+
+pub fn author_submit_extrinsic(xthex_prefixed: &str) -> Value {
+    author_submit_extrinsic_with_id(xthex_prefixed, REQUEST_TRANSFER)
+}
+
+pub fn author_submit_extrinsic_with_id(xthex_prefixed: &str, id: u32) -> Value {
+    json_req("author_submitExtrinsic", xthex_prefixed, id)
+}
+
 pub fn author_submit_and_watch_extrinsic(xthex_prefixed: &str) -> Value {
     author_submit_and_watch_extrinsic_with_id(xthex_prefixed, REQUEST_TRANSFER)
 }
@@ -119,6 +169,43 @@ pub fn author_submit_and_watch_extrinsic_with_id(xthex_prefixed: &str, id: u32)
     json_req("author_submitAndWatchExtrinsic", xthex_prefixed, id)
 }
 
+/// Weight and fee of the extrinsic in the state of block `at`, or the best block if `None`.
+pub fn payment_query_info<H: Encode>(xthex_prefixed: &str, at: Option<H>) -> Value {
+    json!({
+        "method": "payment_queryInfo",
+        "params": [xthex_prefixed, hex_hash(at)],
+        "jsonrpc": "2.0",
+        "id": "1",
+    })
+}
+
+/// Applies the extrinsic to the state of block `at`, or the best block if `None`, without
+/// including it.
+pub fn system_dry_run<H: Encode>(xthex_prefixed: &str, at: Option<H>) -> Value {
+    json!({
+        "method": "system_dryRun",
+        "params": [xthex_prefixed, hex_hash(at)],
+        "jsonrpc": "2.0",
+        "id": "1",
+    })
+}
+
+/// Calls the runtime api `method`, e.g. `Core_version`, with the hex encoded `data` in the state
+/// of block `at`, or the best block if `None`.
+pub fn state_call<H: Encode>(method: &str, data: &str, at: Option<H>) -> Value {
+    json!({
+        "method": "state_call",
+        "params": [method, data, hex_hash(at)],
+        "jsonrpc": "2.0",
+        "id": "1",
+    })
+}
+
+/// The block hash `hash`, of the runtime's type, as the node expects it.
+fn hex_hash<H: Encode>(hash: Option<H>) -> Option<String> {
+    hash.map(|hash| format!("0x{}", hex::encode(hash.encode())))
+}
+
 fn json_req(method: &str, params: &str, id: u32) -> Value {
     json!({
         "method": method,
@@ -127,3 +214,45 @@ fn json_req(method: &str, params: &str, id: u32) -> Value {
         "id": id.to_string(),
     })
 }
+
+/// Returns the method that cancels a subscription started with `subscribe_method`, or `None` if
+/// `subscribe_method` does not start a subscription.
+pub fn unsubscribe_method(subscribe_method: &str) -> Option<&'static str> {
+    match subscribe_method {
+        "state_subscribeStorage" => Some("state_unsubscribeStorage"),
+        "state_subscribeRuntimeVersion" => Some("state_unsubscribeRuntimeVersion"),
+        "chain_subscribeNewHeads" => Some("chain_unsubscribeNewHeads"),
+        "chain_subscribeFinalizedHeads" => Some("chain_unsubscribeFinalizedHeads"),
+        "author_submitAndWatchExtrinsic" => Some("author_unwatchExtrinsic"),
+        _ => None,
+    }
+}
+
+/// Returns `true` if a subscription started with `subscribe_method` can be started again after the
+/// connection to the node was lost. Watching an extrinsic can not, as it would be submitted again.
+pub fn is_resumable(subscribe_method: &str) -> bool {
+    unsubscribe_method(subscribe_method).is_some()
+        && subscribe_method != "author_submitAndWatchExtrinsic"
+}
+
+/// Returns the method of the notifications the node sends for a subscription started with
+/// `subscribe_method`.
+pub fn notification_method(subscribe_method: &str) -> Option<&'static str> {
+    match subscribe_method {
+        "state_subscribeStorage" => Some("state_storage"),
+        "state_subscribeRuntimeVersion" => Some("state_runtimeVersion"),
+        "chain_subscribeNewHeads" => Some("chain_newHead"),
+        "chain_subscribeFinalizedHeads" => Some("chain_finalizedHead"),
+        "author_submitAndWatchExtrinsic" => Some("author_extrinsicUpdate"),
+        _ => None,
+    }
+}
+
+pub fn unsubscribe_with_id(method: &str, subscription: &Value, id: u32) -> Value {
+    json!({
+        "method": method,
+        "params": [subscription],
+        "jsonrpc": "2.0",
+        "id": id.to_string(),
+    })
+}
@@ -0,0 +1,315 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::client::{OnMessageFn, ResultSender};
+use super::json_req;
+use super::{RpcError, RpcResult, RpcTransport};
+
+/// In-memory transport that answers requests from a fixed set of responses instead of talking
+/// to a node. Intended for unit tests of code that uses `Api`.
+///
+/// Responses and subscription notifications are registered per JSON-RPC method. All requests
+/// are recorded and can be inspected with `requests()`. Subscriptions get ids of their own,
+/// whatever the id of their request.
+#[derive(Clone, Default)]
+pub struct MockRpcClient {
+    responses: Arc<Mutex<HashMap<String, Value>>>,
+    notifications: Arc<Mutex<HashMap<String, Vec<Value>>>>,
+    requests: Arc<Mutex<Vec<Value>>>,
+    next_id: Arc<AtomicU32>,
+}
+
+impl MockRpcClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every request of `method` with `result`.
+    pub fn with_response(self, method: &str, result: Value) -> Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), result);
+        self
+    }
+
+    /// Sends `results` as notifications to every subscription started with `method`. The
+    /// response to the subscription request itself is registered with `with_response`.
+    pub fn with_notifications(self, method: &str, results: Vec<Value>) -> Self {
+        self.notifications
+            .lock()
+            .unwrap()
+            .insert(method.to_string(), results);
+        self
+    }
+
+    /// All requests that have been sent so far, in order.
+    pub fn requests(&self) -> Vec<Value> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    fn response(&self, jsonreq: &Value) -> RpcResult<(String, Value)> {
+        self.requests.lock().unwrap().push(jsonreq.clone());
+        let method = jsonreq["method"].as_str().unwrap_or_default().to_string();
+        match self.responses.lock().unwrap().get(&method) {
+            Some(result) => Ok((method, result.clone())),
+            None => Err(RpcError::MethodNotSupported(method)),
+        }
+    }
+}
+
+impl RpcTransport for MockRpcClient {
+    fn get_request(&self, jsonreq: Value, _timeout: Duration) -> RpcResult<String> {
+        let (_, result) = self.response(&jsonreq)?;
+        Ok(result.to_string())
+    }
+
+    fn subscribe(
+        &self,
+        mut jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String> {
+        let id = (self.next_id.fetch_add(1, Ordering::SeqCst) + 1).to_string();
+        jsonreq["id"] = Value::String(id.clone());
+        let (method, subscription) = self.response(&jsonreq)?;
+        let response = json!({
+            "jsonrpc": "2.0",
+            "result": subscription,
+            "id": id,
+        });
+        if on_message_fn(&response.to_string(), &result_in) {
+            return Ok(id);
+        }
+
+        let notification_method = json_req::notification_method(&method).unwrap_or_default();
+        let notifications = self
+            .notifications
+            .lock()
+            .unwrap()
+            .get(&method)
+            .cloned()
+            .unwrap_or_default();
+        for result in notifications {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": notification_method,
+                "params": {
+                    "result": result,
+                    "subscription": subscription,
+                },
+            });
+            if on_message_fn(&notification.to_string(), &result_in) {
+                break;
+            }
+        }
+        Ok(id)
+    }
+
+    /// All messages are delivered before `subscribe` returns, so there is nothing to cancel.
+    fn cancel(&self, _id: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::client::{
+        on_extrinsic_msg_until_finalized, on_subscription_msg, parse_status, XtStatus,
+    };
+    use crate::rpc::DEFAULT_REQUEST_TIMEOUT;
+    use crate::{Api, Hash};
+    use codec::Encode;
+    use sp_core::sr25519;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn get_request_returns_registered_result() {
+        let client = MockRpcClient::new().with_response("chain_getBlockHash", json!("0x01"));
+
+        let result = client
+            .get_request(json_req::chain_get_genesis_hash(), DEFAULT_REQUEST_TIMEOUT)
+            .unwrap();
+
+        assert_eq!(result, "\"0x01\"");
+        assert_eq!(client.requests(), vec![json_req::chain_get_genesis_hash()]);
+    }
+
+    #[test]
+    fn get_request_fails_for_unknown_method() {
+        let client = MockRpcClient::new();
+
+        match client.get_request(json_req::state_get_metadata(), DEFAULT_REQUEST_TIMEOUT) {
+            Err(RpcError::MethodNotSupported(method)) => assert_eq!(method, "state_getMetadata"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn batch_request_returns_results_in_request_order() {
+        let client = MockRpcClient::new()
+            .with_response("chain_getBlockHash", json!("0x01"))
+            .with_response("state_getStorage", json!(null));
+
+        let results = client
+            .batch_request(
+                vec![
+                    json_req::state_get_storage("0x26aa"),
+                    json_req::chain_get_genesis_hash(),
+                ],
+                DEFAULT_REQUEST_TIMEOUT,
+            )
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].as_ref().unwrap(), "null");
+        assert_eq!(results[1].as_ref().unwrap(), "\"0x01\"");
+    }
+
+    #[test]
+    fn subscription_receives_notifications_until_finished() {
+        let client = MockRpcClient::new()
+            .with_response("author_submitAndWatchExtrinsic", json!(7185))
+            .with_notifications(
+                "author_submitAndWatchExtrinsic",
+                vec![
+                    json!("ready"),
+                    json!({ "finalized": "0x934385b11c483498e2b5bca64c2e8ef76ad6c74d3372a05595d3a50caf758d52" }),
+                ],
+            );
+        let (result_in, result_out) = channel();
+
+        client
+            .subscribe(
+                json_req::author_submit_and_watch_extrinsic("0x00"),
+                result_in.into(),
+                on_extrinsic_msg_until_finalized,
+            )
+            .unwrap();
+
+        let (status, hash) = parse_status(&result_out.recv().unwrap());
+        assert_eq!(status, XtStatus::Finalized);
+        assert_eq!(
+            hash.unwrap(),
+            "\"0x934385b11c483498e2b5bca64c2e8ef76ad6c74d3372a05595d3a50caf758d52\""
+        );
+    }
+
+    #[test]
+    fn storage_subscription_forwards_change_sets() {
+        let client = MockRpcClient::new()
+            .with_response("state_subscribeStorage", json!(1))
+            .with_notifications(
+                "state_subscribeStorage",
+                vec![json!({ "block": "0x00", "changes": [["0x26aa", "0x0400"]] })],
+            );
+        let (result_in, result_out) = channel();
+
+        client
+            .subscribe(
+                json_req::state_subscribe_storage("0x26aa"),
+                result_in.into(),
+                on_subscription_msg,
+            )
+            .unwrap();
+
+        assert_eq!(result_out.recv().unwrap(), "0x0400");
+    }
+
+    #[test]
+    fn subscriptions_get_ids_of_their_own() {
+        let client = MockRpcClient::new().with_response("state_subscribeStorage", json!(1));
+        let (result_in, _result_out) = channel();
+
+        let first = client
+            .subscribe(
+                json_req::state_subscribe_storage("0x26aa"),
+                result_in.clone().into(),
+                on_subscription_msg,
+            )
+            .unwrap();
+        let second = client
+            .subscribe(
+                json_req::state_subscribe_storage("0x26aa"),
+                result_in.into(),
+                on_subscription_msg,
+            )
+            .unwrap();
+
+        assert_ne!(first, second);
+        let requests = client.requests();
+        assert_eq!(requests[0]["id"], json!(first));
+        assert_eq!(requests[1]["id"], json!(second));
+    }
+
+    #[test]
+    fn api_is_served_from_registered_responses() {
+        let metadata = node_runtime::Runtime::metadata().encode();
+        let client = MockRpcClient::new()
+            .with_response("chain_getBlockHash", json!(Hash::repeat_byte(0x01)))
+            .with_response("state_getMetadata", json!(format!("0x{}", hex::encode(metadata))))
+            .with_response(
+                "state_getRuntimeVersion",
+                json!({
+                    "specName": "node",
+                    "implName": "substrate-node",
+                    "authoringVersion": 10,
+                    "specVersion": 1,
+                    "implVersion": 1,
+                    "apis": [],
+                    "transactionVersion": 1,
+                }),
+            )
+            .with_response(
+                "state_getStorage",
+                json!(format!("0x{}", hex::encode(1000u128.encode()))),
+            )
+            .with_response("author_submitAndWatchExtrinsic", json!(7185))
+            .with_notifications(
+                "author_submitAndWatchExtrinsic",
+                vec![json!("ready"), json!({ "inBlock": Hash::repeat_byte(0x02) })],
+            );
+
+        let api = Api::<sr25519::Pair, MockRpcClient>::new_with_transport(client.clone()).unwrap();
+        assert_eq!(api.genesis_hash, Hash::repeat_byte(0x01));
+        assert_eq!(api.runtime_version.spec_version, 1);
+
+        let issuance: Option<u128> = api.get_storage_value("Balances", "TotalIssuance").unwrap();
+        assert_eq!(issuance, Some(1000));
+        let block = api.send_extrinsic("0x00".into(), XtStatus::InBlock).unwrap();
+        assert_eq!(block, Some(Hash::repeat_byte(0x02)));
+
+        let methods: Vec<Value> =
+            client.requests().iter().map(|req| req["method"].clone()).collect();
+        assert_eq!(
+            methods,
+            vec![
+                json!("chain_getBlockHash"),
+                json!("state_getMetadata"),
+                json!("state_getRuntimeVersion"),
+                json!("state_getStorage"),
+                json!("author_submitAndWatchExtrinsic"),
+            ]
+        );
+    }
+}
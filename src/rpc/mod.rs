@@ -15,57 +15,153 @@
 
 */
 
-use std::sync::mpsc::Sender as ThreadOut;
-use std::thread;
+use std::time::Duration;
 
-use ws::connect;
+use serde::Deserialize;
+use serde_json::Value;
+use support::weights::{DispatchClass, Weight};
 
-use client::*;
-pub use client::XtStatus;
+pub use client::{
+    on_extrinsic_msg_until_finalized, on_extrinsic_msg_until_in_block,
+    on_extrinsic_msg_until_ready, on_get_request_msg, on_notification_msg, on_subscription_msg,
+    on_transaction_status_msg, parse_response, parse_status, parse_transaction_status,
+    timeout_response, OnMessageFn, ResultSender, TransactionStatus, XtStatus,
+};
+pub use failover_client::FailoverRpcClient;
+pub use http_client::HttpRpcClient;
+pub use mock_client::MockRpcClient;
+pub use recording_client::RecordingRpcClient;
+pub use replay_client::ReplayRpcClient;
+pub use ws_client::WsRpcClient;
 
 mod client;
+mod failover_client;
+mod http_client;
 pub mod json_req;
+mod mock_client;
+mod recording_client;
+mod replay_client;
+mod ws_client;
 
-pub fn get(url: String, json_req: String, result_in: ThreadOut<String>) {
-    start_rpc_client_thread(url, json_req, result_in, on_get_request_msg)
+pub type RpcResult<T> = Result<T, RpcError>;
+
+/// Time to wait for the response to a request, unless configured otherwise.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum RpcError {
+    #[error("Websocket error: {0}")]
+    Ws(#[from] ws::Error),
+    #[error("{0}")]
+    JsonRpc(JsonRpcError),
+    #[error("Invalid JSON-RPC message: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("HTTP error: {0}")]
+    Http(String),
+    #[error("Connection to node lost before a response was received")]
+    ConnectionLost,
+    #[error("No response from node within {0:?}")]
+    Timeout(Duration),
+    #[error("Response to request {0} is missing in the batch response")]
+    MissingBatchResponse(String),
+    #[error("No recorded response to {0}")]
+    NotRecorded(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("No healthy endpoint available")]
+    NoHealthyEndpoint,
+    #[error("Endpoint {endpoint} has genesis hash {found}, expected {expected}")]
+    GenesisMismatch {
+        endpoint: usize,
+        expected: String,
+        found: String,
+    },
+    #[error("Method {0} is not supported by this transport")]
+    MethodNotSupported(String),
+    #[error("Subscription {0} requested, but this transport does not support subscriptions")]
+    SubscriptionNotSupported(String),
 }
 
-pub fn send_extrinsic(
-    url: String,
-    json_req: String,
-    result_in: ThreadOut<String>,
-) {
-    start_rpc_client_thread(url, json_req, result_in, on_extrinsic_msg_until_ready)
+/// Error object of a JSON-RPC response.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default)]
+    pub data: Option<Value>,
 }
 
-pub fn send_extrinsic_and_wait_until_finalized(
-    url: String,
-    json_req: String,
-    result_in: ThreadOut<String>,
-) {
-    start_rpc_client_thread(url, json_req, result_in, on_extrinsic_msg_until_finalized)
+impl std::fmt::Display for JsonRpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "JSON-RPC error {}: {}", self.code, self.message)?;
+        if let Some(data) = &self.data {
+            write!(f, " ({})", data)?;
+        }
+        Ok(())
+    }
 }
 
-pub fn start_event_subscriber(url: String, json_req: String, result_in: ThreadOut<String>) {
-    start_rpc_client_thread(url, json_req, result_in, on_subscription_msg)
+/// Weight, class and fee of an extrinsic, as returned by `payment_queryInfo`.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeDispatchInfo<Balance = u128> {
+    pub weight: Weight,
+    pub class: DispatchClass,
+    /// The fee without the tip, which is added on top.
+    pub partial_fee: Balance,
 }
 
-fn start_rpc_client_thread(
-    url: String,
-    jsonreq: String,
-    result_in: ThreadOut<String>,
-    on_message_fn: OnMessageFn,
-) {
-    let _client = thread::Builder::new()
-        .name("client".to_owned())
-        .spawn(move || {
-            connect(url, |out| RpcClient {
-                out,
-                request: jsonreq.clone(),
-                result: result_in.clone(),
-                on_message_fn,
+/// Carries the JSON-RPC requests of an `Api` to the node.
+///
+/// The `id` field of every request is managed by the transport, so the request builders in
+/// `json_req` can be used as they are.
+pub trait RpcTransport {
+    /// Sends a request and blocks until its response has been received. Returns the `result`
+    /// field of the response as json string.
+    ///
+    /// Fails with `RpcError::Timeout` if there is no response within `timeout`. The request is
+    /// then cancelled, so a late response is dropped.
+    fn get_request(&self, jsonreq: Value, timeout: Duration) -> RpcResult<String>;
+
+    /// Sends all requests as one JSON-RPC batch and blocks until all responses have been
+    /// received. Returns the `result` of each response, or the error the node responded with, in
+    /// the order of `jsonreqs`. Transport errors fail the whole batch.
+    ///
+    /// The default implementation sends the requests one after another.
+    fn batch_request(
+        &self,
+        jsonreqs: Vec<Value>,
+        timeout: Duration,
+    ) -> RpcResult<Vec<RpcResult<String>>> {
+        jsonreqs
+            .into_iter()
+            .map(|jsonreq| match self.get_request(jsonreq, timeout) {
+                Err(RpcError::JsonRpc(error)) => Ok(Err(RpcError::JsonRpc(error))),
+                res => res.map(Ok),
             })
-            .unwrap()
-        })
-        .unwrap();
+            .collect()
+    }
+
+    /// Starts a subscription. `on_message_fn` is called for the response and every notification
+    /// of the subscription until it returns `true`, after which the subscription is cancelled.
+    ///
+    /// For methods that do not start a subscription, `on_message_fn` is only called for the
+    /// response. This allows to wait for a response without blocking, as the async api does.
+    ///
+    /// Returns the JSON-RPC id of the request, which can be passed to `cancel`.
+    fn subscribe(
+        &self,
+        jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String>;
+
+    /// Stops waiting for the response to the request with JSON-RPC id `id`, or ends the
+    /// subscription it has started. Unknown ids are ignored.
+    fn cancel(&self, id: &str);
+
+    /// Sends the url of the node to `sender` every time the connection has been re-established
+    /// after it was lost. Subscriptions are resumed by then, but notifications the node sent in
+    /// between are missed. Transports that do not reconnect never send anything.
+    fn subscribe_reconnects(&self, _sender: ResultSender) {}
 }
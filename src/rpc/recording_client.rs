@@ -0,0 +1,211 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::error;
+use serde_json::{json, Value};
+
+use super::client::{parse_response, OnMessageFn, ResultSender};
+use super::{RpcError, RpcResult, RpcTransport};
+
+/// Passes all requests on to another transport and records them, together with every message
+/// the node sends in reply, to a fixture file. The file can be served by `ReplayRpcClient`.
+///
+/// The fixture has one json object per line, e.g.
+/// `{"call":1,"request":{"method":"chain_getBlockHash","params":[0]},"message":{..}}`.
+/// All messages of a request share the `call` number, which orders the requests. A request
+/// that timed out is recorded once, with `"timeout":true` instead of a message. So is a
+/// subscription that is cancelled before it ended, or still open when the recorder is dropped,
+/// after its messages, as waiting for more of them timed out or would have.
+pub struct RecordingRpcClient<T> {
+    client: T,
+    fixture: Arc<Mutex<File>>,
+    next_call: Arc<AtomicU64>,
+    subscriptions: Arc<OpenSubscriptions>,
+}
+
+/// The call number and request of the subscriptions that have not ended, by id.
+struct OpenSubscriptions {
+    fixture: Arc<Mutex<File>>,
+    open: Mutex<HashMap<String, (u64, Value)>>,
+}
+
+impl OpenSubscriptions {
+    /// Records a timeout for the subscription `id`, unless it has ended.
+    fn time_out(&self, id: &str) {
+        if let Some((call, jsonreq)) = self.open.lock().unwrap().remove(id) {
+            record_timeout(&self.fixture, call, &jsonreq);
+        }
+    }
+}
+
+impl Drop for OpenSubscriptions {
+    fn drop(&mut self) {
+        for (_, (call, jsonreq)) in self.open.get_mut().unwrap().drain() {
+            record_timeout(&self.fixture, call, &jsonreq);
+        }
+    }
+}
+
+impl<T: RpcTransport> RecordingRpcClient<T> {
+    /// Records to the file at `path`, which is created or truncated.
+    pub fn new<F: AsRef<Path>>(client: T, path: F) -> RpcResult<Self> {
+        let fixture = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
+        let fixture = Arc::new(Mutex::new(fixture));
+        Ok(Self {
+            client,
+            fixture: fixture.clone(),
+            next_call: Arc::new(AtomicU64::new(1)),
+            subscriptions: Arc::new(OpenSubscriptions {
+                fixture,
+                open: Mutex::new(HashMap::new()),
+            }),
+        })
+    }
+
+    /// Sends the request to the node and returns the receiver of its raw messages.
+    fn send(&self, jsonreq: Value) -> RpcResult<(u64, String, Receiver<String>)> {
+        let (raw_in, raw_out) = channel();
+        let id = self.client.subscribe(jsonreq, raw_in.into(), on_raw_msg)?;
+        Ok((self.next_call.fetch_add(1, Ordering::SeqCst), id, raw_out))
+    }
+}
+
+impl<T> Clone for RecordingRpcClient<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            fixture: self.fixture.clone(),
+            next_call: self.next_call.clone(),
+            subscriptions: self.subscriptions.clone(),
+        }
+    }
+}
+
+impl<T: RpcTransport> RpcTransport for RecordingRpcClient<T> {
+    fn get_request(&self, jsonreq: Value, timeout: Duration) -> RpcResult<String> {
+        let (call, id, raw_out) = self.send(jsonreq.clone())?;
+        let msg = match raw_out.recv_timeout(timeout) {
+            Ok(msg) => msg,
+            Err(RecvTimeoutError::Timeout) => {
+                self.client.cancel(&id);
+                record_timeout(&self.fixture, call, &jsonreq);
+                return Err(RpcError::Timeout(timeout));
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(RpcError::ConnectionLost),
+        };
+        record(&self.fixture, call, &jsonreq, &msg);
+        parse_response(&msg)
+    }
+
+    fn subscribe(
+        &self,
+        jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String> {
+        let (call, id, raw_out) = self.send(jsonreq.clone())?;
+        self.subscriptions
+            .open
+            .lock()
+            .unwrap()
+            .insert(id.clone(), (call, jsonreq.clone()));
+        let fixture = self.fixture.clone();
+        // the recorder may be dropped before the subscription ends
+        let subscriptions = Arc::downgrade(&self.subscriptions);
+        let thread_id = id.clone();
+        thread::Builder::new()
+            .name("recorder".to_owned())
+            .spawn(move || {
+                // ends when the transport drops the subscription, or `on_message_fn` is done
+                // and dropping `raw_out` makes the transport drop it
+                for msg in raw_out.iter() {
+                    record(&fixture, call, &jsonreq, &msg);
+                    if on_message_fn(&msg, &result_in) {
+                        break;
+                    }
+                }
+                if let Some(subscriptions) = subscriptions.upgrade() {
+                    subscriptions.open.lock().unwrap().remove(&thread_id);
+                }
+            })?;
+        Ok(id)
+    }
+
+    fn cancel(&self, id: &str) {
+        self.subscriptions.time_out(id);
+        self.client.cancel(id)
+    }
+
+    fn subscribe_reconnects(&self, sender: ResultSender) {
+        self.client.subscribe_reconnects(sender)
+    }
+}
+
+/// Forwards every message as it is. Returns `true` once the recorder has hung up.
+fn on_raw_msg(msg: &str, result: &ResultSender) -> bool {
+    result.send(msg.to_string()).is_err()
+}
+
+/// The part of a request that identifies its response, i.e. without the `id`.
+pub(crate) fn request_key(jsonreq: &Value) -> Value {
+    json!({
+        "method": jsonreq["method"],
+        "params": jsonreq["params"],
+    })
+}
+
+fn record(fixture: &Mutex<File>, call: u64, jsonreq: &Value, msg: &str) {
+    let message: Value = serde_json::from_str(msg).unwrap_or_else(|_| Value::String(msg.into()));
+    let line = json!({
+        "call": call,
+        "request": request_key(jsonreq),
+        "message": message,
+    });
+    write_line(fixture, &line);
+}
+
+fn record_timeout(fixture: &Mutex<File>, call: u64, jsonreq: &Value) {
+    let line = json!({
+        "call": call,
+        "request": request_key(jsonreq),
+        "timeout": true,
+    });
+    write_line(fixture, &line);
+}
+
+fn write_line(fixture: &Mutex<File>, line: &Value) {
+    if let Err(e) = writeln!(fixture.lock().unwrap(), "{}", line) {
+        error!("could not record message: {:?}", e);
+    }
+}
@@ -0,0 +1,334 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::client::{parse_response, OnMessageFn, ResultSender};
+use super::recording_client::request_key;
+use super::{RpcError, RpcResult, RpcTransport};
+
+/// Serves the requests from a fixture recorded by `RecordingRpcClient`, so code using `Api` can
+/// be tested without a node.
+///
+/// Requests are matched by method and params. Extrinsic submissions are matched by method only,
+/// as the signatures differ on every run, so they are served in the order they were recorded.
+/// If a request has been recorded several times, the recorded calls are served in order, and the
+/// last one is repeated once all have been served. Requests that timed out while recording time
+/// out again. All messages of a subscription are delivered before `subscribe` returns. A
+/// subscription recorded with a timeout stays open until cancelled, so waiting for more of its
+/// messages times out again.
+#[derive(Clone)]
+pub struct ReplayRpcClient {
+    calls: Arc<Mutex<HashMap<String, VecDeque<RecordedCall>>>>,
+    next_id: Arc<AtomicU32>,
+    /// Subscriptions that stay open, by id.
+    open: Arc<Mutex<HashMap<String, ResultSender>>>,
+}
+
+#[derive(Clone, Default)]
+struct RecordedCall {
+    messages: Vec<Value>,
+    timed_out: bool,
+}
+
+impl ReplayRpcClient {
+    pub fn new<F: AsRef<Path>>(path: F) -> RpcResult<Self> {
+        // all calls, ordered by call number
+        let mut recorded: BTreeMap<u64, (String, RecordedCall)> = BTreeMap::new();
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Value = serde_json::from_str(&line)?;
+            let call = entry["call"].as_u64().unwrap_or_default();
+            let (_, recorded_call) = recorded
+                .entry(call)
+                .or_insert_with(|| (call_key(&entry["request"]), RecordedCall::default()));
+            if entry["timeout"].as_bool().unwrap_or(false) {
+                recorded_call.timed_out = true;
+            } else {
+                recorded_call.messages.push(entry["message"].clone());
+            }
+        }
+
+        let mut calls: HashMap<String, VecDeque<RecordedCall>> = HashMap::new();
+        for (_, (key, call)) in recorded {
+            calls.entry(key).or_default().push_back(call);
+        }
+        Ok(Self {
+            calls: Arc::new(Mutex::new(calls)),
+            next_id: Arc::new(AtomicU32::new(1)),
+            open: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Returns the next recorded call of the request.
+    fn next_call(&self, jsonreq: &Value) -> RpcResult<RecordedCall> {
+        let key = call_key(&request_key(jsonreq));
+        let mut calls = self.calls.lock().unwrap();
+        let recorded = calls
+            .get_mut(&key)
+            .filter(|recorded| !recorded.is_empty())
+            .ok_or_else(|| RpcError::NotRecorded(key.clone()))?;
+        Ok(if recorded.len() > 1 {
+            recorded.pop_front().unwrap()
+        } else {
+            recorded[0].clone()
+        })
+    }
+}
+
+/// Identifies the recorded calls of the request with `request_key`.
+fn call_key(request_key: &Value) -> String {
+    match request_key["method"].as_str() {
+        Some(method) if method.starts_with("author_submit") => json!({ "method": method }),
+        _ => request_key.clone(),
+    }
+    .to_string()
+}
+
+impl RpcTransport for ReplayRpcClient {
+    fn get_request(&self, jsonreq: Value, timeout: Duration) -> RpcResult<String> {
+        let call = self.next_call(&jsonreq)?;
+        if call.timed_out {
+            return Err(RpcError::Timeout(timeout));
+        }
+        let response = call
+            .messages
+            .first()
+            .ok_or_else(|| RpcError::NotRecorded(request_key(&jsonreq).to_string()))?;
+        parse_response(&response.to_string())
+    }
+
+    fn subscribe(
+        &self,
+        jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String> {
+        let call = self.next_call(&jsonreq)?;
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst).to_string();
+        let mut ended = false;
+        for mut msg in call.messages {
+            // the response carries the id of the recorded request
+            if msg.get("id").is_some() {
+                msg["id"] = Value::String(id.clone());
+            }
+            if on_message_fn(&msg.to_string(), &result_in) {
+                ended = true;
+                break;
+            }
+        }
+        if call.timed_out && !ended {
+            self.open.lock().unwrap().insert(id.clone(), result_in);
+        }
+        Ok(id)
+    }
+
+    /// Ends a subscription that was recorded with a timeout.
+    fn cancel(&self, id: &str) {
+        self.open.lock().unwrap().remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::client::{
+        on_extrinsic_msg_until_finalized, on_subscription_msg, parse_status, XtStatus,
+    };
+    use crate::rpc::{json_req, MockRpcClient, RecordingRpcClient, DEFAULT_REQUEST_TIMEOUT};
+    use crate::{Api, ApiError, Hash};
+    use keyring::AccountKeyring;
+    use serde_json::json;
+    use sp_core::crypto::AccountId32;
+    use sp_core::sr25519;
+    use std::sync::mpsc::{channel, RecvTimeoutError};
+
+    #[test]
+    fn replays_recorded_requests_and_subscriptions() {
+        let path = std::env::temp_dir().join(format!("replay-{}.jsonl", std::process::id()));
+        let node = MockRpcClient::new()
+            .with_response("chain_getBlockHash", json!("0x01"))
+            .with_response("author_submitAndWatchExtrinsic", json!(7185))
+            .with_notifications(
+                "author_submitAndWatchExtrinsic",
+                vec![json!("ready"), json!({ "finalized": "0x02" })],
+            );
+
+        let recorder = RecordingRpcClient::new(node, &path).unwrap();
+        let hash = recorder
+            .get_request(json_req::chain_get_genesis_hash(), DEFAULT_REQUEST_TIMEOUT)
+            .unwrap();
+        assert_eq!(hash, "\"0x01\"");
+        let (result_in, result_out) = channel();
+        recorder
+            .subscribe(
+                json_req::author_submit_and_watch_extrinsic("0x00"),
+                result_in.into(),
+                on_extrinsic_msg_until_finalized,
+            )
+            .unwrap();
+        result_out.recv().unwrap();
+
+        let replay = ReplayRpcClient::new(&path).unwrap();
+        let hash = replay
+            .get_request(json_req::chain_get_genesis_hash(), DEFAULT_REQUEST_TIMEOUT)
+            .unwrap();
+        assert_eq!(hash, "\"0x01\"");
+        // extrinsics are matched regardless of their signature, which differs on every run
+        let (result_in, result_out) = channel();
+        replay
+            .subscribe(
+                json_req::author_submit_and_watch_extrinsic("0x01"),
+                result_in.into(),
+                on_extrinsic_msg_until_finalized,
+            )
+            .unwrap();
+        let (status, hash) = parse_status(&result_out.recv().unwrap());
+        assert_eq!(status, XtStatus::Finalized);
+        assert_eq!(hash.unwrap(), "\"0x02\"");
+
+        match replay.get_request(json_req::state_get_metadata(), DEFAULT_REQUEST_TIMEOUT) {
+            Err(RpcError::NotRecorded(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    /// Never answers.
+    #[derive(Default)]
+    struct Silent {
+        pending: Mutex<Vec<ResultSender>>,
+    }
+
+    impl RpcTransport for Silent {
+        fn get_request(&self, _jsonreq: Value, timeout: Duration) -> RpcResult<String> {
+            Err(RpcError::Timeout(timeout))
+        }
+
+        fn subscribe(
+            &self,
+            _jsonreq: Value,
+            result_in: ResultSender,
+            _on_message_fn: OnMessageFn,
+        ) -> RpcResult<String> {
+            self.pending.lock().unwrap().push(result_in);
+            Ok("1".into())
+        }
+
+        fn cancel(&self, _id: &str) {}
+    }
+
+    #[test]
+    fn timeouts_are_replayed() {
+        let path =
+            std::env::temp_dir().join(format!("replay-timeout-{}.jsonl", std::process::id()));
+        let timeout = Duration::from_millis(50);
+
+        let recorder = RecordingRpcClient::new(Silent::default(), &path).unwrap();
+        match recorder.get_request(json_req::chain_get_header::<Hash>(None), timeout) {
+            Err(RpcError::Timeout(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let replay = ReplayRpcClient::new(&path).unwrap();
+        match replay.get_request(json_req::chain_get_header::<Hash>(None), timeout) {
+            Err(RpcError::Timeout(t)) => assert_eq!(t, timeout),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn api_is_served_from_a_recorded_fixture() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/rpc/fixtures/transfer.jsonl");
+        let client = ReplayRpcClient::new(path).unwrap();
+        let api = Api::<sr25519::Pair, ReplayRpcClient>::new_with_transport(client)
+            .unwrap()
+            .set_finalization_timeout(Duration::from_millis(200));
+        assert_eq!(api.genesis_hash, Hash::repeat_byte(0x01));
+        let issuance: Option<u128> = api.get_storage_value("Balances", "TotalIssuance").unwrap();
+        assert_eq!(issuance, Some(1000));
+
+        let (events_in, events_out) = channel();
+        api.subscribe_events(events_in).unwrap();
+        // extrinsics are matched by method only
+        let block = api.send_extrinsic("0x00".into(), XtStatus::InBlock).unwrap();
+        assert_eq!(block, Some(Hash::repeat_byte(0x02)));
+
+        let (from, to, value) = api
+            .wait_for_event::<(AccountId32, AccountId32, u128)>("Balances", "Transfer", &events_out)
+            .unwrap();
+        assert_eq!(from, AccountKeyring::Alice.to_account_id());
+        assert_eq!(to, AccountKeyring::Bob.to_account_id());
+        assert_eq!(value, 42);
+        // the subscription timed out while recording, so waiting for another event does too
+        match api.wait_for_event::<(AccountId32, AccountId32, u128)>(
+            "Balances",
+            "Transfer",
+            &events_out,
+        ) {
+            Err(ApiError::Timeout(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn subscriptions_open_when_the_recorder_is_dropped_time_out_when_replayed() {
+        let path = std::env::temp_dir().join(format!("replay-open-{}.jsonl", std::process::id()));
+        let recorder = RecordingRpcClient::new(Silent::default(), &path).unwrap();
+        let (result_in, _result_out) = channel();
+        recorder
+            .subscribe(
+                json_req::state_subscribe_storage("0x00"),
+                result_in.into(),
+                on_subscription_msg,
+            )
+            .unwrap();
+        drop(recorder);
+
+        let replay = ReplayRpcClient::new(&path).unwrap();
+        let (result_in, result_out) = channel::<String>();
+        let id = replay
+            .subscribe(
+                json_req::state_subscribe_storage("0x00"),
+                result_in.into(),
+                on_subscription_msg,
+            )
+            .unwrap();
+        assert_eq!(
+            result_out.recv_timeout(Duration::from_millis(50)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        replay.cancel(&id);
+        assert_eq!(
+            result_out.recv_timeout(Duration::from_millis(50)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,501 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+use std::cmp::min;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{debug, error, warn};
+use serde_json::Value;
+use ws::{connect, CloseCode, Error, ErrorKind, Result};
+
+use super::client::*;
+use super::{RpcError, RpcResult, RpcTransport};
+
+/// Time to wait before reconnecting after the connection was lost.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// The time between reconnection attempts doubles with each failed attempt, up to this limit.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A single, long-lived websocket connection to the node.
+///
+/// Requests of all clones of a `WsRpcClient` are multiplexed over the same connection. Every
+/// request gets a unique JSON-RPC id, which is used to route the node's response back to the
+/// caller. Subscriptions are routed by the subscription id the node assigns to them.
+///
+/// If the connection is lost, the client reconnects with exponential backoff. Pending requests
+/// fail with `RpcError::ConnectionLost`, while subscriptions are started again on the new
+/// connection, except for watched extrinsics. Use `subscribe_reconnects` to learn about
+/// notifications that may have been missed in between.
+#[derive(Clone)]
+pub struct WsRpcClient {
+    url: String,
+    connection: Connection,
+    next_id: Arc<AtomicU32>,
+    requests: PendingRequests,
+    subscriptions: Subscriptions,
+    reconnect_listeners: ReconnectListeners,
+    _shutdown: Arc<Shutdown>,
+}
+
+/// Closes the connection and stops reconnecting once the last clone of a client is dropped.
+struct Shutdown {
+    connection: Connection,
+    closed: Arc<AtomicBool>,
+}
+
+impl Drop for Shutdown {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::SeqCst);
+        if let Some(out) = self.connection.lock().unwrap().as_ref() {
+            if let Err(e) = out.close(CloseCode::Normal) {
+                error!("could not close websocket connection: {:?}", e);
+            }
+        }
+    }
+}
+
+impl WsRpcClient {
+    /// Connects to the node at `url` and blocks until the connection is established.
+    pub fn new(url: &str) -> Result<Self> {
+        Self::start(url, true)
+    }
+
+    /// Like `new`, but returns right away and keeps trying to connect in the background if the
+    /// node is not reachable. Requests fail with `RpcError::ConnectionLost` until connected.
+    pub fn connect_in_background(url: &str) -> Result<Self> {
+        Self::start(url, false)
+    }
+
+    fn start(url: &str, wait_for_connection: bool) -> Result<Self> {
+        let connection: Connection = Arc::new(Mutex::new(None));
+        let next_id = Arc::new(AtomicU32::new(1));
+        let requests: PendingRequests = Arc::new(Mutex::new(HashMap::new()));
+        let subscriptions: Subscriptions = Arc::new(Mutex::new(HashMap::new()));
+        let reconnect_listeners: ReconnectListeners = Arc::new(Mutex::new(Vec::new()));
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let (ready_in, ready_out) = channel();
+        let thread_url = url.to_string();
+        // the thread stops reconnecting once all clients are dropped
+        let thread_closed = closed.clone();
+        let thread_connection = connection.clone();
+        let thread_next_id = next_id.clone();
+        let thread_requests = requests.clone();
+        let thread_subscriptions = subscriptions.clone();
+        let thread_listeners = reconnect_listeners.clone();
+        thread::Builder::new()
+            .name("client".to_owned())
+            .spawn(move || {
+                let mut backoff = INITIAL_BACKOFF;
+                let mut reconnected = false;
+                while !thread_closed.load(Ordering::SeqCst) {
+                    let opened = Arc::new(AtomicBool::new(false));
+                    let res = connect(thread_url.clone(), |out| RpcClient {
+                        url: thread_url.clone(),
+                        out,
+                        ready: ready_in.clone(),
+                        connection: thread_connection.clone(),
+                        next_id: thread_next_id.clone(),
+                        requests: thread_requests.clone(),
+                        subscriptions: thread_subscriptions.clone(),
+                        reconnect_listeners: thread_listeners.clone(),
+                        reconnected,
+                        opened: opened.clone(),
+                        closed: thread_closed.clone(),
+                    });
+                    if let Err(e) = res {
+                        error!("websocket connection to {} failed: {:?}", thread_url, e);
+                    }
+
+                    let was_connected = opened.load(Ordering::SeqCst);
+                    *thread_connection.lock().unwrap() = None;
+                    on_connection_lost(&thread_requests, &thread_subscriptions);
+                    if thread_closed.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if !was_connected && !reconnected && wait_for_connection {
+                        // the first connection failed, which `new` reports
+                        break;
+                    }
+
+                    backoff = if was_connected {
+                        INITIAL_BACKOFF
+                    } else {
+                        min(backoff * 2, MAX_BACKOFF)
+                    };
+                    warn!("reconnecting to {} in {:?}", thread_url, backoff);
+                    thread::sleep(backoff);
+                    reconnected = true;
+                }
+            })?;
+
+        if wait_for_connection {
+            ready_out
+                .recv()
+                .map_err(|_| Error::new(ErrorKind::Internal, "could not connect to node"))?;
+        }
+
+        Ok(Self {
+            url: url.to_string(),
+            connection: connection.clone(),
+            next_id,
+            requests,
+            subscriptions,
+            reconnect_listeners,
+            _shutdown: Arc::new(Shutdown { connection, closed }),
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Number of requests waiting for a response and of active subscriptions.
+    #[cfg(test)]
+    pub(crate) fn pending_requests(&self) -> usize {
+        self.requests.lock().unwrap().len() + self.subscriptions.lock().unwrap().len()
+    }
+
+    fn send_request(
+        &self,
+        jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String> {
+        let mut ids = self.send(vec![(jsonreq, result_in, on_message_fn)], false)?;
+        Ok(ids.remove(0))
+    }
+
+    /// Registers the requests with a new id each and sends them, as batch if `batch` is set.
+    /// Returns the ids in the order of `requests`.
+    fn send(
+        &self,
+        requests: Vec<(Value, ResultSender, OnMessageFn)>,
+        batch: bool,
+    ) -> RpcResult<Vec<String>> {
+        // hold the connection until the requests are registered, see `on_connection_lost`
+        let connection = self.connection.lock().unwrap();
+        let out = connection.as_ref().ok_or(RpcError::ConnectionLost)?;
+
+        let mut ids = Vec::with_capacity(requests.len());
+        let mut jsonreqs = Vec::with_capacity(requests.len());
+        for (mut jsonreq, result_in, on_message_fn) in requests {
+            let id = self.next_id.fetch_add(1, Ordering::SeqCst).to_string();
+            jsonreq["id"] = Value::String(id.clone());
+            let method = jsonreq["method"].as_str().unwrap_or_default().to_string();
+            self.requests.lock().unwrap().insert(
+                id.clone(),
+                PendingRequest {
+                    id: id.clone(),
+                    method,
+                    jsonreq: jsonreq.clone(),
+                    result: result_in,
+                    on_message_fn,
+                },
+            );
+            ids.push(id);
+            jsonreqs.push(jsonreq);
+        }
+
+        let msg = if batch {
+            Value::Array(jsonreqs).to_string()
+        } else {
+            jsonreqs.remove(0).to_string()
+        };
+        debug!("sending request: {}", msg);
+        if let Err(e) = out.send(msg) {
+            let mut requests = self.requests.lock().unwrap();
+            for id in &ids {
+                requests.remove(id);
+            }
+            return Err(e.into());
+        }
+        Ok(ids)
+    }
+
+    /// Waits for the response on `result_out` until `deadline` and cancels the request `id` if
+    /// there is none by then.
+    fn recv_response(
+        &self,
+        id: &str,
+        result_out: &Receiver<String>,
+        deadline: Instant,
+        timeout: Duration,
+    ) -> RpcResult<String> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match result_out.recv_timeout(remaining) {
+            Ok(msg) => Ok(msg),
+            Err(RecvTimeoutError::Timeout) => {
+                self.cancel(id);
+                Err(RpcError::Timeout(timeout))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(RpcError::ConnectionLost),
+        }
+    }
+}
+
+impl RpcTransport for WsRpcClient {
+    fn get_request(&self, jsonreq: Value, timeout: Duration) -> RpcResult<String> {
+        let (result_in, result_out) = channel();
+        let id = self.send_request(jsonreq, result_in.into(), on_get_request_msg)?;
+        let msg = self.recv_response(&id, &result_out, Instant::now() + timeout, timeout)?;
+        parse_response(&msg)
+    }
+
+    fn batch_request(
+        &self,
+        jsonreqs: Vec<Value>,
+        timeout: Duration,
+    ) -> RpcResult<Vec<RpcResult<String>>> {
+        if jsonreqs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let deadline = Instant::now() + timeout;
+        let (requests, receivers): (Vec<_>, Vec<_>) = jsonreqs
+            .into_iter()
+            .map(|jsonreq| {
+                let (result_in, result_out) = channel();
+                let on_message_fn: OnMessageFn = on_get_request_msg;
+                ((jsonreq, result_in.into(), on_message_fn), result_out)
+            })
+            .unzip();
+        let ids = self.send(requests, true)?;
+
+        let mut results = Vec::with_capacity(ids.len());
+        for (i, (id, result_out)) in ids.iter().zip(receivers.iter()).enumerate() {
+            match self.recv_response(id, result_out, deadline, timeout) {
+                Ok(msg) => results.push(parse_response(&msg)),
+                Err(e) => {
+                    for id in &ids[i + 1..] {
+                        self.cancel(id);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    fn subscribe(
+        &self,
+        jsonreq: Value,
+        result_in: ResultSender,
+        on_message_fn: OnMessageFn,
+    ) -> RpcResult<String> {
+        self.send_request(jsonreq, result_in, on_message_fn)
+    }
+
+    fn cancel(&self, id: &str) {
+        let mut requests = self.requests.lock().unwrap();
+        let count = requests.len();
+        requests.retain(|_, request| request.id != id);
+        if requests.len() < count {
+            debug!("cancelled request {}", id);
+            return;
+        }
+        drop(requests);
+
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let subscription = subscriptions
+            .iter()
+            .find(|(_, sub)| sub.id == id)
+            .map(|(subscription, _)| subscription.clone());
+        if let Some(subscription) = subscription {
+            let sub = subscriptions.remove(&subscription).unwrap();
+            drop(subscriptions);
+            // the map is keyed by the subscription id as json
+            let subscription: Value = serde_json::from_str(&subscription).unwrap_or_default();
+            if let Some(out) = self.connection.lock().unwrap().as_ref() {
+                if let Err(e) = unsubscribe(out, &self.next_id, &sub.method, &subscription) {
+                    error!("could not cancel subscription {}: {:?}", subscription, e);
+                }
+            }
+        }
+    }
+
+    fn subscribe_reconnects(&self, sender: ResultSender) {
+        self.reconnect_listeners.lock().unwrap().push(sender);
+    }
+}
+
+#[cfg(all(test, feature = "test-support"))]
+mod tests {
+    use super::*;
+    use crate::rpc::json_req;
+    use crate::test_support::{FakeNode, FakeNodeHandle, ScriptedStatus};
+    use crate::Hash;
+    use serde_json::json;
+
+    const TIMEOUT: Duration = Duration::from_millis(200);
+
+    fn node() -> FakeNode {
+        FakeNode::new(node_runtime::Runtime::metadata())
+    }
+
+    #[test]
+    fn timed_out_request_is_forgotten() {
+        let node = node().with_unanswered("chain_getHeader").spawn().unwrap();
+        let client = WsRpcClient::new(&node.url()).unwrap();
+
+        match client.get_request(json_req::chain_get_header::<Hash>(None), TIMEOUT) {
+            Err(RpcError::Timeout(timeout)) => assert_eq!(timeout, TIMEOUT),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(client.requests.lock().unwrap().is_empty());
+        assert!(client
+            .get_request(json_req::chain_get_genesis_hash(), TIMEOUT)
+            .is_ok());
+    }
+
+    #[test]
+    fn batch_responses_are_routed_by_id() {
+        let node = node().spawn().unwrap();
+        let client = WsRpcClient::new(&node.url()).unwrap();
+        let unknown = json!({ "jsonrpc": "2.0", "method": "foo_bar", "params": [], "id": "1" });
+
+        let results = client
+            .batch_request(
+                vec![
+                    json_req::chain_get_genesis_hash(),
+                    unknown,
+                    json_req::chain_get_block_hash(Some(2)),
+                ],
+                TIMEOUT,
+            )
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &hash_result(Hash::repeat_byte(1)));
+        match &results[1] {
+            Err(RpcError::JsonRpc(error)) => assert_eq!(error.code, -32601),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(results[2].as_ref().unwrap(), &hash_result(Hash::from_low_u64_be(2)));
+        assert_eq!(node.requests().len(), 3);
+        assert!(client.requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn cancelled_subscription_is_ended() {
+        let node = node()
+            .with_extrinsic_statuses(vec![ScriptedStatus::Ready])
+            .spawn()
+            .unwrap();
+        let client = WsRpcClient::new(&node.url()).unwrap();
+        let (result_in, result_out) = channel();
+
+        let id = client
+            .subscribe(
+                json_req::author_submit_and_watch_extrinsic("0x00"),
+                result_in.into(),
+                on_transaction_status_msg,
+            )
+            .unwrap();
+        // the subscription is registered once its first notification arrives
+        result_out.recv_timeout(TIMEOUT).unwrap();
+        assert_eq!(client.subscriptions.lock().unwrap().len(), 1);
+
+        client.cancel(&id);
+
+        assert!(client.subscriptions.lock().unwrap().is_empty());
+        assert!(client.requests.lock().unwrap().is_empty());
+        assert!(result_out.recv_timeout(TIMEOUT).is_err());
+    }
+
+    #[test]
+    fn requests_and_subscriptions_work_again_after_the_node_restarts() {
+        let key = vec![1u8, 2, 3];
+        let node = node().with_storage(key.clone(), vec![4]).spawn().unwrap();
+        let url = node.url();
+        let client = WsRpcClient::new(&url).unwrap();
+        let (reconnects_in, reconnects_out) = channel();
+        client.subscribe_reconnects(reconnects_in.into());
+        let (storage_in, storage_out) = channel();
+        client
+            .subscribe(
+                json_req::state_subscribe_storage("0x010203"),
+                storage_in.into(),
+                on_subscription_msg,
+            )
+            .unwrap();
+        assert_eq!(storage_out.recv_timeout(TIMEOUT).unwrap(), "0x04");
+
+        let mut node = Some(node);
+        // the second restart checks that the client keeps reconnecting
+        for _ in 0..2 {
+            drop(node.take());
+            node = Some(restart(&url, key.clone()));
+
+            let reconnected = reconnects_out.recv_timeout(Duration::from_secs(10));
+            assert_eq!(reconnected.unwrap(), url);
+            let storage = storage_out.recv_timeout(Duration::from_secs(1));
+            assert_eq!(storage.unwrap(), "0x04");
+            assert!(client
+                .get_request(json_req::chain_get_genesis_hash(), TIMEOUT)
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn connection_is_closed_once_all_clients_are_dropped() {
+        let node = node().spawn().unwrap();
+        let client = WsRpcClient::new(&node.url()).unwrap();
+        let clone = client.clone();
+        wait_for_connections(&node, 1);
+
+        drop(client);
+        assert!(clone
+            .get_request(json_req::chain_get_genesis_hash(), TIMEOUT)
+            .is_ok());
+        drop(clone);
+
+        wait_for_connections(&node, 0);
+    }
+
+    /// Waits for `count` clients to be connected to `node`, for at most a second.
+    fn wait_for_connections(node: &FakeNodeHandle, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while node.connections() != count {
+            assert!(Instant::now() < deadline, "{} connections", node.connections());
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// The `result` of a response with `hash`, as returned by `get_request`.
+    fn hash_result(hash: Hash) -> String {
+        json!(format!("0x{}", hex::encode(hash))).to_string()
+    }
+
+    /// Starts a new node at the address of the stopped one, which may take a moment to free it.
+    fn restart(url: &str, key: Vec<u8>) -> FakeNodeHandle {
+        let addr = url.trim_start_matches("ws://");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match node().with_storage(key.clone(), vec![4]).spawn_at(addr) {
+                Ok(node) => return node,
+                Err(e) if Instant::now() < deadline => debug!("restart failed: {:?}", e),
+                Err(e) => panic!("could not restart node: {:?}", e),
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
@@ -0,0 +1,74 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! The primitive types of a chain. `Api`, the account types, the events decoder and the
+//! extrinsics are generic over a `Runtime`, which defaults to the substrate node's.
+
+use core::convert::TryFrom;
+use core::fmt::Debug;
+
+use codec::{Codec, HasCompact};
+use sp_core::H256;
+use sp_runtime::AccountId32;
+
+use crate::extrinsic::xt_primitives::{DefaultSignedExtra, GenericAddress, SignedExtra};
+
+/// Primitive types of a chain, mirroring the ones of its `frame_system::Trait`. Implement it on
+/// a marker type to talk to chains with different types than the substrate node:
+///
+/// ```ignore
+/// #[derive(Clone, Debug)]
+/// pub struct MyChain;
+///
+/// impl Runtime for MyChain {
+///     type Index = u32;
+///     type BlockNumber = u32;
+///     type Hash = H256;
+///     type AccountId = AccountId32;
+///     type Address = AccountId32;
+///     type Balance = u64;
+///     type SignedExtra = MySignedExtra;
+/// }
+/// ```
+pub trait Runtime {
+    /// Index of a transaction, the account nonce.
+    type Index: Codec + Default + Copy + Debug + Send + 'static + TryFrom<u64>;
+    type BlockNumber: Codec + Default + Copy + Debug + Send + 'static + Into<u64> + TryFrom<u64>;
+    type Hash: Codec + Default + Copy + Debug + Send + 'static;
+    /// Provided by the `signer::Signer` of the extrinsics.
+    type AccountId: Codec + Default + Clone + Debug + Send + 'static;
+    /// How extrinsics refer to the signer, e.g. the account id itself.
+    type Address: Codec + Clone + Debug + From<Self::AccountId>;
+    type Balance: Codec + HasCompact + Default + Copy + Debug + Send + 'static;
+    /// The signed extensions of the runtime.
+    type SignedExtra: SignedExtra<Index = Self::Index, Balance = Self::Balance, Hash = Self::Hash>;
+}
+
+/// The types of the substrate node.
+#[cfg_attr(feature = "std", derive(Debug))]
+#[derive(Clone, Eq, PartialEq)]
+pub struct DefaultRuntime;
+
+impl Runtime for DefaultRuntime {
+    type Index = u32;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type AccountId = AccountId32;
+    type Address = GenericAddress;
+    type Balance = u128;
+    type SignedExtra = DefaultSignedExtra;
+}
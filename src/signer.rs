@@ -0,0 +1,131 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! Signers of extrinsics. The sr25519, ed25519 and ecdsa key pairs of `sp_core` are signers,
+//! keys held elsewhere, e.g. in a hardware module or a remote service, can be used by
+//! implementing `Signer`. See `src/examples/example_remote_signer.rs`.
+
+use core::convert::Infallible;
+use core::fmt::Debug;
+
+use codec::Encode;
+use sp_core::crypto::Pair;
+use sp_core::{ecdsa, ed25519, sr25519};
+use sp_runtime::traits::IdentifyAccount;
+use sp_runtime::{AccountId32, MultiSignature, MultiSigner};
+
+use crate::extrinsic::xt_primitives::{ExtraParamsOf, SignedExtra, SignedPayload, UncheckedExtrinsicV4};
+use crate::runtime::Runtime;
+
+/// Signs extrinsics on behalf of an account.
+pub trait Signer<AccountId = AccountId32> {
+    type Error: Debug;
+
+    /// The account that pays for and is the origin of the signed extrinsics.
+    fn account_id(&self) -> AccountId;
+
+    /// Signs the encoded `SignedPayload` of an extrinsic.
+    fn sign(&self, payload: &[u8]) -> Result<MultiSignature, Self::Error>;
+}
+
+macro_rules! impl_signer_for_pair {
+    ($($crypto: ident),*) => {
+        $(
+            impl Signer for $crypto::Pair {
+                type Error = Infallible;
+
+                fn account_id(&self) -> AccountId32 {
+                    MultiSigner::from(self.public()).into_account()
+                }
+
+                fn sign(&self, payload: &[u8]) -> Result<MultiSignature, Infallible> {
+                    Ok(Pair::sign(self, payload).into())
+                }
+            }
+        )*
+    };
+}
+
+// The account of an ecdsa key is the blake2 hash of its 33 byte public key, like in substrate.
+impl_signer_for_pair!(sr25519, ed25519, ecdsa);
+
+/// Signs `call` with `signer` for runtime `R`. This is what `compose_extrinsic_offline!` does,
+/// but it returns the error of a fallible signer instead of panicking.
+pub fn sign_extrinsic<Call, R, S>(
+    signer: &S,
+    call: Call,
+    params: &ExtraParamsOf<R::SignedExtra>,
+) -> Result<UncheckedExtrinsicV4<Call, R>, S::Error>
+where
+    Call: Encode + Clone,
+    R: Runtime,
+    S: Signer<R::AccountId> + ?Sized,
+{
+    let raw_payload = SignedPayload::<_, R::SignedExtra>::new(call.clone(), params);
+    let signature = raw_payload.using_encoded(|payload| signer.sign(payload))?;
+    Ok(UncheckedExtrinsicV4::new_signed(
+        call,
+        signer.account_id().into(),
+        signature,
+        R::SignedExtra::extra(params),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extrinsic::xt_primitives::{Era, ExtraParams, GenericExtra};
+    use crate::runtime::DefaultRuntime;
+    use sp_core::{blake2_256, H256};
+    use sp_runtime::traits::Verify;
+
+    #[test]
+    fn ecdsa_account_is_the_hash_of_the_public_key() {
+        let pair = ecdsa::Pair::from_string("//Alice", None).unwrap();
+        let public = pair.public();
+
+        assert_eq!(public.as_ref().len(), 33);
+        assert_eq!(
+            Signer::account_id(&pair),
+            AccountId32::from(blake2_256(public.as_ref()))
+        );
+    }
+
+    #[test]
+    fn ecdsa_signature_verifies_against_the_account() {
+        let pair = ecdsa::Pair::from_string("//Alice", None).unwrap();
+        let params = ExtraParams {
+            nonce: 2,
+            tip: 0,
+            era: Era::Immortal,
+            era_checkpoint: H256::repeat_byte(1),
+            genesis_hash: H256::repeat_byte(1),
+            spec_version: 1,
+            custom: (),
+        };
+
+        let xt = sign_extrinsic::<_, DefaultRuntime, _>(&pair, vec![4u8, 5, 6], &params).unwrap();
+
+        let (signer, signature, extra) = xt.signature.unwrap();
+        let payload = SignedPayload::<_>::new(vec![4u8, 5, 6], &params).encode();
+        assert_eq!(signer, Signer::account_id(&pair));
+        assert_eq!(extra, GenericExtra::new(2));
+        assert!(matches!(signature, MultiSignature::Ecdsa(_)));
+        assert!(signature.verify(&payload[..], &signer));
+        assert!(!signature.verify(&b"other"[..], &signer));
+    }
+}
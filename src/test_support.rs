@@ -0,0 +1,651 @@
+/*
+   Copyright 2019 Supercomputing Systems AG
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+
+*/
+
+//! In-process fake node for integration tests, enabled with the `test-support` feature.
+//!
+//! `FakeNode` serves the JSON-RPC methods `Api` needs over a websocket on localhost, backed by
+//! an in-memory storage map and the metadata of a real runtime:
+//!
+//! ```ignore
+//! let node = FakeNode::new(node_runtime::Runtime::metadata())
+//!     .with_storage(key, value)
+//!     .with_extrinsic_statuses(vec![ScriptedStatus::Ready, ScriptedStatus::Finalized(hash)])
+//!     .spawn()?;
+//! let api = Api::<sr25519::Pair>::new(node.url())?;
+//! ```
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use codec::Encode;
+use log::{debug, warn};
+use metadata::RuntimeMetadataPrefixed;
+use serde_json::{json, Value};
+use sp_core::blake2_256;
+use ws::{CloseCode, Handler, Handshake, Message, Result, Sender, WebSocket};
+
+use crate::Hash;
+
+/// Status notification sent for every extrinsic submitted with `author_submitAndWatchExtrinsic`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScriptedStatus {
+    Future,
+    Ready,
+    Broadcast(Vec<String>),
+    InBlock(Hash),
+    Retracted(Hash),
+    Finalized(Hash),
+    Usurped(Hash),
+    Dropped,
+    Invalid,
+}
+
+impl ScriptedStatus {
+    fn to_json(&self) -> Value {
+        match self {
+            ScriptedStatus::Future => json!("future"),
+            ScriptedStatus::Ready => json!("ready"),
+            ScriptedStatus::Broadcast(peers) => json!({ "broadcast": peers }),
+            ScriptedStatus::InBlock(hash) => json!({ "inBlock": hex_prefixed(hash.as_bytes()) }),
+            ScriptedStatus::Retracted(hash) => {
+                json!({ "retracted": hex_prefixed(hash.as_bytes()) })
+            }
+            ScriptedStatus::Finalized(hash) => {
+                json!({ "finalized": hex_prefixed(hash.as_bytes()) })
+            }
+            ScriptedStatus::Usurped(hash) => json!({ "usurped": hex_prefixed(hash.as_bytes()) }),
+            ScriptedStatus::Dropped => json!("dropped"),
+            ScriptedStatus::Invalid => json!("invalid"),
+        }
+    }
+}
+
+/// Configuration of a fake node. Start it with `spawn`.
+pub struct FakeNode {
+    state: State,
+}
+
+struct State {
+    genesis_hash: Hash,
+    best_number: u64,
+    metadata: Vec<u8>,
+    runtime_version: Value,
+    /// Storage values by their hex encoded key.
+    storage: HashMap<String, Vec<u8>>,
+    extrinsic_statuses: Vec<ScriptedStatus>,
+    submitted_extrinsics: Vec<String>,
+    storage_subscriptions: Vec<StorageSubscription>,
+    /// Fixed results of other methods.
+    responses: HashMap<String, Value>,
+    /// Methods whose requests are answered with an error code and message.
+    errors: HashMap<String, (i64, String)>,
+    /// Methods whose requests are never answered.
+    unanswered: Vec<String>,
+    /// Every request received, in order.
+    requests: Vec<Value>,
+    /// Number of open client connections.
+    connections: usize,
+}
+
+struct StorageSubscription {
+    out: Sender,
+    id: u64,
+    keys: Vec<String>,
+}
+
+impl FakeNode {
+    /// A node of the runtime with `metadata`, e.g. `node_runtime::Runtime::metadata()`.
+    pub fn new(metadata: RuntimeMetadataPrefixed) -> Self {
+        Self {
+            state: State {
+                genesis_hash: Hash::repeat_byte(0x01),
+                best_number: 0,
+                metadata: metadata.encode(),
+                runtime_version: json!({
+                    "specName": "node",
+                    "implName": "substrate-node",
+                    "authoringVersion": 10,
+                    "specVersion": 1,
+                    "implVersion": 1,
+                    "apis": [],
+                    "transactionVersion": 1,
+                }),
+                storage: HashMap::new(),
+                extrinsic_statuses: vec![
+                    ScriptedStatus::Ready,
+                    ScriptedStatus::InBlock(Hash::repeat_byte(0x02)),
+                    ScriptedStatus::Finalized(Hash::repeat_byte(0x02)),
+                ],
+                submitted_extrinsics: Vec::new(),
+                storage_subscriptions: Vec::new(),
+                responses: HashMap::new(),
+                errors: HashMap::new(),
+                unanswered: Vec::new(),
+                requests: Vec::new(),
+                connections: 0,
+            },
+        }
+    }
+
+    pub fn with_genesis_hash(mut self, genesis_hash: Hash) -> Self {
+        self.state.genesis_hash = genesis_hash;
+        self
+    }
+
+    /// Number of the best block, 0 by default. The hash of block `n > 0` is
+    /// `Hash::from_low_u64_be(n)`.
+    pub fn with_best_block_number(mut self, number: u64) -> Self {
+        self.state.best_number = number;
+        self
+    }
+
+    /// Answers `state_getRuntimeVersion` with `runtime_version`.
+    pub fn with_runtime_version(mut self, runtime_version: Value) -> Self {
+        self.state.runtime_version = runtime_version;
+        self
+    }
+
+    /// Sets the SCALE encoded `value` at the storage `key`, e.g. `StorageKey.0`.
+    pub fn with_storage(mut self, key: Vec<u8>, value: Vec<u8>) -> Self {
+        self.state.storage.insert(hex_prefixed(&key), value);
+        self
+    }
+
+    /// The statuses sent for every watched extrinsic, in order. By default ready, in block and
+    /// finalized.
+    pub fn with_extrinsic_statuses(mut self, statuses: Vec<ScriptedStatus>) -> Self {
+        self.state.extrinsic_statuses = statuses;
+        self
+    }
+
+    /// Answers every request of `method` with `result`, e.g. `payment_queryInfo`.
+    pub fn with_response(mut self, method: &str, result: Value) -> Self {
+        self.state.responses.insert(method.to_owned(), result);
+        self
+    }
+
+    /// Answers every request of `method` with an error, e.g. `1010` for an
+    /// `author_submitExtrinsic` the runtime rejects.
+    pub fn with_error(mut self, method: &str, code: i64, message: &str) -> Self {
+        self.state
+            .errors
+            .insert(method.to_owned(), (code, message.to_owned()));
+        self
+    }
+
+    /// Never answers requests of `method`, e.g. to let them time out.
+    pub fn with_unanswered(mut self, method: &str) -> Self {
+        self.state.unanswered.push(method.to_owned());
+        self
+    }
+
+    /// Starts listening on a free port on localhost.
+    pub fn spawn(self) -> Result<FakeNodeHandle> {
+        self.spawn_at("127.0.0.1:0")
+    }
+
+    /// Starts listening on `addr`, e.g. the one of a stopped node to let its clients reconnect.
+    pub fn spawn_at(self, addr: &str) -> Result<FakeNodeHandle> {
+        let state = Arc::new(Mutex::new(self.state));
+        let next_subscription = Arc::new(AtomicU64::new(1));
+        let server_state = state.clone();
+        let socket = WebSocket::new(move |out| FakeNodeConnection {
+            out,
+            state: server_state.clone(),
+            next_subscription: next_subscription.clone(),
+            batch: RefCell::new(None),
+        })?
+        .bind(addr)?;
+        let url = format!("ws://{}", socket.local_addr()?);
+        let broadcaster = socket.broadcaster();
+        thread::Builder::new()
+            .name("fake-node".to_owned())
+            .spawn(move || {
+                if let Err(e) = socket.run() {
+                    warn!("fake node stopped: {:?}", e);
+                }
+            })?;
+        Ok(FakeNodeHandle {
+            url,
+            state,
+            broadcaster,
+        })
+    }
+}
+
+/// A running fake node. It is shut down when the handle is dropped.
+pub struct FakeNodeHandle {
+    url: String,
+    state: Arc<Mutex<State>>,
+    broadcaster: Sender,
+}
+
+impl FakeNodeHandle {
+    /// Url to connect to, e.g. with `Api::new`.
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Changes the storage and notifies the subscribers of `key`, e.g. about new events.
+    pub fn set_storage(&self, key: Vec<u8>, value: Vec<u8>) {
+        let key = hex_prefixed(&key);
+        let mut state = self.state.lock().unwrap();
+        state.storage.insert(key.clone(), value.clone());
+        for subscription in &state.storage_subscriptions {
+            if subscription.keys.contains(&key) {
+                let notification = storage_notification(
+                    subscription.id,
+                    &state.genesis_hash,
+                    vec![(key.clone(), Some(value.clone()))],
+                );
+                let _ = subscription.out.send(notification.to_string());
+            }
+        }
+    }
+
+    /// Answers the following requests of `method` with `result`, see `FakeNode::with_response`.
+    pub fn set_response(&self, method: &str, result: Value) {
+        let mut state = self.state.lock().unwrap();
+        state.responses.insert(method.to_owned(), result);
+    }
+
+    /// All extrinsics submitted so far, hex encoded.
+    pub fn submitted_extrinsics(&self) -> Vec<String> {
+        self.state.lock().unwrap().submitted_extrinsics.clone()
+    }
+
+    /// All requests received so far, in order.
+    pub fn requests(&self) -> Vec<Value> {
+        self.state.lock().unwrap().requests.clone()
+    }
+
+    /// The requests of `method` received so far, in order.
+    pub fn requests_of(&self, method: &str) -> Vec<Value> {
+        self.requests()
+            .into_iter()
+            .filter(|request| request["method"] == method)
+            .collect()
+    }
+
+    /// The params of the requests of `method` received so far, in order.
+    pub fn params_of(&self, method: &str) -> Vec<Value> {
+        self.requests_of(method)
+            .into_iter()
+            .map(|mut request| request["params"].take())
+            .collect()
+    }
+
+    /// Number of clients connected right now.
+    pub fn connections(&self) -> usize {
+        self.state.lock().unwrap().connections
+    }
+}
+
+impl Drop for FakeNodeHandle {
+    fn drop(&mut self) {
+        let _ = self.broadcaster.shutdown();
+    }
+}
+
+struct FakeNodeConnection {
+    out: Sender,
+    state: Arc<Mutex<State>>,
+    next_subscription: Arc<AtomicU64>,
+    /// Responses to the batch being answered.
+    batch: RefCell<Option<Vec<Value>>>,
+}
+
+impl Handler for FakeNodeConnection {
+    fn on_open(&mut self, _: Handshake) -> Result<()> {
+        self.state.lock().unwrap().connections += 1;
+        Ok(())
+    }
+
+    fn on_message(&mut self, msg: Message) -> Result<()> {
+        let request: Value = match serde_json::from_str(msg.as_text()?) {
+            Ok(request) => request,
+            Err(_) => return self.respond(&Value::Null, Err((-32700, "Parse error"))),
+        };
+        if let Value::Array(requests) = &request {
+            // the responses to a batch are sent together, in reverse order like a node may
+            self.batch.replace(Some(Vec::new()));
+            for request in requests {
+                self.on_request(request)?;
+            }
+            let mut responses = self.batch.replace(None).unwrap_or_default();
+            responses.reverse();
+            return self.out.send(Value::Array(responses).to_string());
+        }
+        self.on_request(&request)
+    }
+
+    fn on_close(&mut self, _: CloseCode, _: &str) {
+        let connection = self.out.connection_id();
+        let mut state = self.state.lock().unwrap();
+        state.connections = state.connections.saturating_sub(1);
+        state
+            .storage_subscriptions
+            .retain(|sub| sub.out.connection_id() != connection);
+    }
+}
+
+impl FakeNodeConnection {
+    fn on_request(&self, request: &Value) -> Result<()> {
+        debug!("fake node got request {}", request);
+        let id = &request["id"];
+        let params = &request["params"];
+        let method = request["method"].as_str().unwrap_or_default();
+        {
+            let mut state = self.state.lock().unwrap();
+            state.requests.push(request.clone());
+            if state.unanswered.iter().any(|unanswered| unanswered == method) {
+                return Ok(());
+            }
+            if let Some((code, message)) = state.errors.get(method).cloned() {
+                drop(state);
+                return self.respond(id, Err((code, &message)));
+            }
+        }
+        match method {
+            "chain_getBlockHash" => {
+                let state = self.state.lock().unwrap();
+                let number = params[0].as_u64().unwrap_or(state.best_number);
+                let hash = match number {
+                    0 => state.genesis_hash,
+                    _ => Hash::from_low_u64_be(number),
+                };
+                self.respond(id, Ok(json!(hex_prefixed(hash.as_bytes()))))
+            }
+            "chain_getHeader" => {
+                let number = self.state.lock().unwrap().best_number;
+                let header = json!({
+                    "parentHash": hex_prefixed(Hash::zero().as_bytes()),
+                    "number": format!("0x{:x}", number),
+                    "stateRoot": hex_prefixed(Hash::zero().as_bytes()),
+                    "extrinsicsRoot": hex_prefixed(Hash::zero().as_bytes()),
+                    "digest": { "logs": [] },
+                });
+                self.respond(id, Ok(header))
+            }
+            "chain_getBlock" => {
+                // every submitted extrinsic is in the one and only block
+                let extrinsics = self.state.lock().unwrap().submitted_extrinsics.clone();
+                let block = json!({
+                    "block": { "header": Value::Null, "extrinsics": extrinsics },
+                    "justification": Value::Null,
+                });
+                self.respond(id, Ok(block))
+            }
+            "state_getMetadata" => {
+                let metadata = hex_prefixed(&self.state.lock().unwrap().metadata);
+                self.respond(id, Ok(json!(metadata)))
+            }
+            "state_getRuntimeVersion" => {
+                let version = self.state.lock().unwrap().runtime_version.clone();
+                self.respond(id, Ok(version))
+            }
+            "state_getStorage" => {
+                let key = params[0].as_str().unwrap_or_default().to_lowercase();
+                let value = self.state.lock().unwrap().storage.get(&key).cloned();
+                self.respond(id, Ok(json!(value.map(|v| hex_prefixed(&v)))))
+            }
+            "state_subscribeStorage" => self.subscribe_storage(id, params),
+            "state_unsubscribeStorage" => {
+                let subscription = params[0].as_u64().unwrap_or_default();
+                self.state
+                    .lock()
+                    .unwrap()
+                    .storage_subscriptions
+                    .retain(|sub| sub.id != subscription);
+                self.respond(id, Ok(json!(true)))
+            }
+            "author_submitExtrinsic" => {
+                let hash = self.submit(params);
+                self.respond(id, Ok(json!(hash)))
+            }
+            "author_submitAndWatchExtrinsic" => {
+                self.submit(params);
+                let subscription = self.next_subscription.fetch_add(1, Ordering::SeqCst);
+                self.respond(id, Ok(json!(subscription)))?;
+                let statuses = self.state.lock().unwrap().extrinsic_statuses.clone();
+                for status in statuses {
+                    let notification = json!({
+                        "jsonrpc": "2.0",
+                        "method": "author_extrinsicUpdate",
+                        "params": { "result": status.to_json(), "subscription": subscription },
+                    });
+                    self.out.send(notification.to_string())?;
+                }
+                Ok(())
+            }
+            "author_unwatchExtrinsic" => self.respond(id, Ok(json!(true))),
+            "system_health" => self.respond(
+                id,
+                Ok(json!({ "peers": 1, "isSyncing": false, "shouldHavePeers": true })),
+            ),
+            method => {
+                let result = self.state.lock().unwrap().responses.get(method).cloned();
+                match result {
+                    Some(result) => self.respond(id, Ok(result)),
+                    None => self.respond(id, Err((-32601, "Method not found"))),
+                }
+            }
+        }
+    }
+
+    fn respond(&self, id: &Value, result: std::result::Result<Value, (i64, &str)>) -> Result<()> {
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "error": { "code": code, "message": message },
+                "id": id,
+            }),
+        };
+        match self.batch.borrow_mut().as_mut() {
+            Some(responses) => {
+                responses.push(response);
+                Ok(())
+            }
+            None => self.out.send(response.to_string()),
+        }
+    }
+
+    fn subscribe_storage(&self, id: &Value, params: &Value) -> Result<()> {
+        let keys: Vec<String> = params[0]
+            .as_array()
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|key| key.as_str())
+                    .map(|key| key.to_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let subscription = self.next_subscription.fetch_add(1, Ordering::SeqCst);
+        self.respond(id, Ok(json!(subscription)))?;
+
+        // like a node, start with the current values
+        let mut state = self.state.lock().unwrap();
+        let changes = keys
+            .iter()
+            .map(|key| (key.clone(), state.storage.get(key).cloned()))
+            .collect();
+        let notification = storage_notification(subscription, &state.genesis_hash, changes);
+        self.out.send(notification.to_string())?;
+        state.storage_subscriptions.push(StorageSubscription {
+            out: self.out.clone(),
+            id: subscription,
+            keys,
+        });
+        Ok(())
+    }
+
+    /// Records the extrinsic and returns its hash.
+    fn submit(&self, params: &Value) -> String {
+        let xt = params[0].as_str().unwrap_or_default().to_string();
+        let bytes = hex::decode(xt.trim_start_matches("0x")).unwrap_or_default();
+        self.state.lock().unwrap().submitted_extrinsics.push(xt);
+        hex_prefixed(&blake2_256(&bytes))
+    }
+}
+
+fn storage_notification(
+    subscription: u64,
+    block: &Hash,
+    changes: Vec<(String, Option<Vec<u8>>)>,
+) -> Value {
+    let changes: Vec<Value> = changes
+        .into_iter()
+        .map(|(key, value)| json!([key, value.map(|v| hex_prefixed(&v))]))
+        .collect();
+    json!({
+        "jsonrpc": "2.0",
+        "method": "state_storage",
+        "params": {
+            "result": { "block": hex_prefixed(block.as_bytes()), "changes": changes },
+            "subscription": subscription,
+        },
+    })
+}
+
+pub(crate) fn hex_prefixed(bytes: &[u8]) -> String {
+    format!("0x{}", hex::encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::node;
+    use super::*;
+    use crate::{Api, XtStatus};
+    use sp_core::sr25519;
+
+    #[test]
+    fn api_reads_storage_from_fake_node() {
+        let node = node().spawn().unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url()).unwrap();
+        let key = api
+            .metadata
+            .module("Balances")
+            .unwrap()
+            .storage("TotalIssuance")
+            .unwrap()
+            .get_value()
+            .unwrap()
+            .key();
+        node.set_storage(key.0, 1_000u128.encode());
+
+        let total: Option<u128> = api.get_storage_value("Balances", "TotalIssuance").unwrap();
+
+        assert_eq!(api.genesis_hash, Hash::repeat_byte(0x01));
+        assert_eq!(total, Some(1_000));
+    }
+
+    #[test]
+    fn extrinsic_status_sequence_is_scripted() {
+        let finalized = Hash::repeat_byte(0x03);
+        let node = node()
+            .with_extrinsic_statuses(vec![
+                ScriptedStatus::Ready,
+                ScriptedStatus::Finalized(finalized),
+            ])
+            .spawn()
+            .unwrap();
+        let api = Api::<sr25519::Pair>::new(node.url()).unwrap();
+
+        let hash = api
+            .send_extrinsic("0x00".to_string(), XtStatus::Finalized)
+            .unwrap();
+
+        assert_eq!(hash, Some(finalized));
+        assert_eq!(node.submitted_extrinsics(), vec!["0x00".to_string()]);
+    }
+}
+
+/// Nodes, apis and extrinsics shared by the tests of the crate.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+    use crate::extrinsic::balances::BalanceTransferXt;
+    use crate::extrinsic::xt_primitives::UncheckedExtrinsicV4;
+    use crate::signer::Signer;
+    use crate::Api;
+    use keyring::AccountKeyring;
+    use sp_core::sr25519;
+    use sp_runtime::{AccountId32, MultiSignature};
+    use std::time::{Duration, Instant};
+
+    pub const TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// A node of the substrate node's runtime.
+    pub fn node() -> FakeNode {
+        FakeNode::new(node_runtime::Runtime::metadata())
+    }
+
+    /// An api that signs with Alice's key.
+    pub fn alice(node: &FakeNodeHandle) -> Api<sr25519::Pair> {
+        Api::new(node.url())
+            .unwrap()
+            .set_signer(AccountKeyring::Alice.pair())
+    }
+
+    /// A running node that answers every request of `method` with `result`, and `alice` on it.
+    pub fn answering(method: &str, result: Value) -> (FakeNodeHandle, Api<sr25519::Pair>) {
+        let node = node().with_response(method, result).spawn().unwrap();
+        let api = alice(&node);
+        (node, api)
+    }
+
+    /// A transfer of 42 from the signer of `api` to Bob.
+    pub fn transfer(api: &Api<sr25519::Pair>) -> BalanceTransferXt {
+        api.balance_transfer(AccountKeyring::Bob.to_account_id(), 42).unwrap()
+    }
+
+    /// The nonce `xt` is signed with.
+    pub fn nonce_of<Call>(xt: &UncheckedExtrinsicV4<Call>) -> u32 {
+        xt.signature.as_ref().unwrap().2.nonce()
+    }
+
+    /// Waits for the node to receive a request of `method`, for at most a second.
+    pub fn wait_for_request(node: &FakeNodeHandle, method: &str) -> Value {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        loop {
+            if let Some(request) = node.requests_of(method).pop() {
+                return request;
+            }
+            assert!(Instant::now() < deadline, "no {} request", method);
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// A signer whose key is out of reach.
+    pub struct UnpluggedSigner;
+
+    impl Signer for UnpluggedSigner {
+        type Error = &'static str;
+
+        fn account_id(&self) -> AccountId32 {
+            AccountKeyring::Alice.to_account_id()
+        }
+
+        fn sign(&self, _: &[u8]) -> std::result::Result<MultiSignature, &'static str> {
+            Err("unplugged")
+        }
+    }
+}
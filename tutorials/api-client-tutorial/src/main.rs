@@ -44,7 +44,8 @@ fn main() {
         "KittyModule",
         "create_kitty",
         10 as u128
-    );
+    )
+    .unwrap();
 
     println!("[+] Extrinsic: {:?}\n", xt);
 